@@ -0,0 +1,53 @@
+-- Tables created before migrations existed, so they may already be there
+CREATE TABLE IF NOT EXISTS classes (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY,
+    username TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE TABLE IF NOT EXISTS user_classes (
+    user_id INTEGER NOT NULL,
+    class_id INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (class_id) REFERENCES classes (id)
+);
+CREATE TABLE IF NOT EXISTS homeworks (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    class_id INTEGER NOT NULL,
+    created_by INTEGER,
+    lesson_id INTEGER,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT,
+    FOREIGN KEY (class_id) REFERENCES classes (id),
+    FOREIGN KEY (created_by) REFERENCES users (id)
+);
+CREATE TABLE IF NOT EXISTS lesson_types (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE TABLE IF NOT EXISTS lessons (
+    id INTEGER PRIMARY KEY,
+    type_id INTEGER NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT,
+    FOREIGN KEY (type_id) REFERENCES lesson_types (id)
+);
+CREATE TABLE IF NOT EXISTS lesson_classes (
+    lesson_id INTEGER NOT NULL,
+    class_id INTEGER NOT NULL,
+    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
+    FOREIGN KEY (class_id) REFERENCES classes (id)
+);
@@ -0,0 +1,13 @@
+-- Memberships and the classes of a lesson can be soft deleted like the other rows
+ALTER TABLE user_classes ADD COLUMN deleted_at TEXT;
+ALTER TABLE lesson_classes ADD COLUMN deleted_at TEXT;
+
+-- A user is in a class (and a lesson is for a class) at most once
+DELETE FROM user_classes WHERE rowid NOT IN (
+    SELECT MIN(rowid) FROM user_classes GROUP BY user_id, class_id
+);
+DELETE FROM lesson_classes WHERE rowid NOT IN (
+    SELECT MIN(rowid) FROM lesson_classes GROUP BY lesson_id, class_id
+);
+CREATE UNIQUE INDEX user_classes_user_class ON user_classes (user_id, class_id);
+CREATE UNIQUE INDEX lesson_classes_lesson_class ON lesson_classes (lesson_id, class_id);
@@ -0,0 +1,3 @@
+-- Date for which the homework must be done. The homeworks created before are due the day they were created
+ALTER TABLE homeworks ADD COLUMN due_date TEXT;
+UPDATE homeworks SET due_date = date(created_at) WHERE due_date IS NULL;
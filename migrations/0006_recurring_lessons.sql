@@ -0,0 +1,36 @@
+-- A lesson repeats every `repeat_weeks` weeks (1 for every week, 2 for A/B weeks) until `repeat_until` (included)
+ALTER TABLE lessons ADD COLUMN repeat_weeks INTEGER;
+ALTER TABLE lessons ADD COLUMN repeat_until TEXT;
+
+CREATE TABLE rooms (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+
+-- Changes to one occurrence of a lesson, `date` is the day it was planned
+CREATE TABLE lesson_exceptions (
+    lesson_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    cancelled INTEGER NOT NULL DEFAULT 0,
+    -- New times when the occurrence is moved
+    start TEXT,
+    end TEXT,
+    -- Room replacing the rooms of the lesson for this occurrence
+    room_id INTEGER,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (lesson_id, date),
+    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
+    FOREIGN KEY (room_id) REFERENCES rooms (id)
+);
+
+-- Days without lessons, from `start` to `end` included
+CREATE TABLE holidays (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    start TEXT NOT NULL,
+    end TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
@@ -0,0 +1,22 @@
+-- Rooms where the lesson usually takes place, an exception can change them for one occurrence
+CREATE TABLE lesson_rooms (
+    lesson_id INTEGER NOT NULL,
+    room_id INTEGER NOT NULL,
+    deleted_at TEXT,
+    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
+    FOREIGN KEY (room_id) REFERENCES rooms (id)
+);
+CREATE UNIQUE INDEX lesson_rooms_unique ON lesson_rooms (lesson_id, room_id);
+
+-- Lessons saved by an admin despite their conflicts with other lessons
+CREATE TABLE conflict_overrides (
+    id INTEGER PRIMARY KEY,
+    lesson_id INTEGER NOT NULL,
+    user_id INTEGER,
+    reason TEXT NOT NULL,
+    -- Description of the conflicts that were ignored
+    conflicts TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
@@ -0,0 +1,28 @@
+-- A teacher may have an account on the site
+CREATE TABLE teachers (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    user_id INTEGER UNIQUE,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+CREATE TABLE lesson_teachers (
+    lesson_id INTEGER NOT NULL,
+    teacher_id INTEGER NOT NULL,
+    deleted_at TEXT,
+    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
+    FOREIGN KEY (teacher_id) REFERENCES teachers (id)
+);
+CREATE UNIQUE INDEX lesson_teachers_unique ON lesson_teachers (lesson_id, teacher_id);
+
+-- The teachers of a lesson were the teachers of its classes
+INSERT INTO teachers (name, user_id, created_at)
+SELECT username, id, datetime('now') FROM users
+WHERE id IN (SELECT user_id FROM user_classes WHERE role = 'teacher' AND deleted_at IS NULL);
+INSERT OR IGNORE INTO lesson_teachers (lesson_id, teacher_id)
+SELECT lesson_classes.lesson_id, teachers.id FROM lesson_classes
+JOIN user_classes ON user_classes.class_id = lesson_classes.class_id
+    AND user_classes.role = 'teacher' AND user_classes.deleted_at IS NULL
+JOIN teachers ON teachers.user_id = user_classes.user_id
+WHERE lesson_classes.deleted_at IS NULL;
@@ -0,0 +1,22 @@
+-- Code given to the students so they can join a class, replaced by a new one when it leaks
+ALTER TABLE classes ADD COLUMN join_code TEXT;
+UPDATE classes SET join_code = upper(hex(randomblob(4)));
+CREATE UNIQUE INDEX classes_join_code ON classes (join_code);
+
+-- Audit log of the changes to the members of the classes
+CREATE TABLE membership_changes (
+    id INTEGER PRIMARY KEY,
+    class_id INTEGER NOT NULL,
+    -- User who made the change, NULL once they are removed from the database
+    actor_id INTEGER,
+    -- Member added, removed or whose role changed, NULL when the join code was changed
+    user_id INTEGER,
+    action TEXT NOT NULL,
+    -- New role of the member
+    role TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (class_id) REFERENCES classes (id),
+    FOREIGN KEY (actor_id) REFERENCES users (id),
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+CREATE INDEX membership_changes_class ON membership_changes (class_id);
@@ -0,0 +1,4 @@
+-- Two accounts can't have the same name, ignoring the case. The names taken twice before are made unique
+UPDATE users SET username = username || ' (' || id || ')'
+WHERE EXISTS (SELECT 1 FROM users AS other WHERE other.username = users.username COLLATE NOCASE AND other.id < users.id);
+CREATE UNIQUE INDEX users_username ON users (username COLLATE NOCASE);
@@ -1,14 +1,11 @@
-use std::{sync::{Arc, atomic::{AtomicU32, Ordering}}, path::PathBuf, fs::File, io::Write, fmt::{format, Display}};
+use std::{sync::{Arc, atomic::{AtomicU32, Ordering}}, collections::HashMap, path::PathBuf, fs::File, io::Write};
 
-use aes::cipher::{KeyIvInit, BlockEncryptMut, block_padding::Pkcs7};
 use headless_chrome::{Browser, browser::Tab, protocol::cdp::{Page::CaptureScreenshotFormatOption, Target::CreateTarget}};
 use anyhow::{Result, anyhow, Context};
 use tokio::sync::RwLock;
+use serde_json::json;
+use crate::pronote::{crypto::{Crypto, RSA_1024, WrongKey}, instance::Instance, login::{self, LoginError}, messages::{self, Security}, types::{Authentification, DonneesSec, FonctionParametres, Identification, Message, Request}};
 
-type AES_CBC_encryptor = cbc::Encryptor<aes::Aes128>;
-
-const PRONOTE_URL: &str = "https://0332768e.index-education.net/pronote/viescolaire.html";
-const DEMO_PRONOTE_URL: &str = "https://demo.index-education.net/pronote/eleve.html";
 const NORMAL_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/116.0.0.0 Safari/537.36";
 const PROXY_URL: &str = "https://51.38.82.225:80";
 
@@ -23,13 +20,14 @@ pub enum Page {
 pub struct HeadlessBrowserAPIClient {
     pub browser: Arc<Browser>,
     pub tab: Arc<Tab>,
-    pub page: Arc<RwLock<Page>>
+    pub page: Arc<RwLock<Page>>,
+    pub instance: Instance
 }
 impl HeadlessBrowserAPIClient {
     pub fn new_browser() -> Result<Arc<Browser>> {
         Ok(Arc::new(Browser::default()?))
     }
-    pub fn new(browser: Option<Arc<Browser>>, demo:bool) -> Result<Self> {
+    pub fn new(browser: Option<Arc<Browser>>, instance: Instance) -> Result<Self> {
         let browser = match browser {
             Some(b) => b,
             None => Self::new_browser()?
@@ -38,7 +36,7 @@ impl HeadlessBrowserAPIClient {
         let tab = browser.new_tab_with_options(CreateTarget {
             height: Some(1080),
             width: Some(1920),
-            url: (if demo {DEMO_PRONOTE_URL} else {PRONOTE_URL}).to_string(),
+            url: instance.page_url(),
             browser_context_id: Some(context.get_id().to_string()),
             enable_begin_frame_control: None,
             new_window: None,
@@ -47,7 +45,8 @@ impl HeadlessBrowserAPIClient {
         Ok(Self {
             browser,
             tab,
-            page: Arc::new(RwLock::new(Page::Login))
+            page: Arc::new(RwLock::new(Page::Login)),
+            instance
         })
     }
     pub async fn auth(&self, username: &str, password: &str) -> Result<()> {
@@ -103,7 +102,7 @@ impl HeadlessBrowserAPIClient {
                 Some(date) => match date.parse::<u32>() {
                     Err(_) => return Err(anyhow!("Invalid date (invalid day of month)")),
                     Ok(date) => {
-                        if date < 1 || date > 31 {
+                        if !(1..=31).contains(&date) {
                             return Err(anyhow!("Invalid date (day of month)"))
                         }
                         date.try_into().unwrap()
@@ -185,131 +184,147 @@ impl HeadlessBrowserAPIClient {
     }
 }
 
-pub enum EncryptArgs {
-    String(String),
-    Bytes(Vec<u8>)
-}
-impl From<String> for EncryptArgs {
-    fn from(s: String) -> Self {
-        Self::String(s)
-    }
-}
-impl From<u32> for EncryptArgs {
-    fn from(i: u32) -> Self {
-        Self::String(i.to_string())
-    }
-}
-impl From<Vec<u8>> for EncryptArgs {
-    fn from(b: Vec<u8>) -> Self {
-        Self::Bytes(b)
-    }
+/// Parameters of the web client in the page of the instance, ex: `Start ({h:'9435671',d:true,sCrA:true,sCoA:true,poll:true,a:3})`
+pub(crate) fn parse_start_params(html: &str) -> Result<HashMap<String, String>> {
+    let params = html.split_once("Start (")
+        .and_then(|(_, rest)| rest.split_once('{'))
+        .and_then(|(_, rest)| rest.split_once('}'))
+        .ok_or(anyhow!("No parameters in the page : {}", html))?
+        .0;
+    Ok(params.split(',')
+        .filter_map(|param| param.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().trim_matches('\'').to_string()))
+        .collect())
 }
 
 pub struct APIClient {
     pub client: reqwest::Client,
+    pub instance: Instance,
     pub session_id: u32,
     pub numero_ordre: Arc<AtomicU32>,
-    pub key_aes: [u8; 16],
-    pub iv_aes: [u8; 16]
+    pub crypto: Crypto,
+    /// The instance is served over HTTP, the IV sent to it must be RSA encrypted
+    pub http: bool,
+    pub security: Security
 }
 impl APIClient {
-    pub fn new() -> Self {
+    pub fn new(instance: Instance) -> Self {
         Self {
             client: reqwest::Client::builder()
                 .proxy(reqwest::Proxy::http(PROXY_URL).unwrap())
                 .build().unwrap(),
+            instance,
             session_id: 0,
             numero_ordre: Arc::new(AtomicU32::new(1)),
-            key_aes: [0; 16],
-            iv_aes: rand::random::<[u8; 16]>()
+            crypto: Crypto::new(),
+            http: false,
+            security: Security { encrypt: false, compress: false }
         }
     }
-    pub fn encrypt<'a>(&self, data: &'a mut EncryptArgs) -> Result<&'a [u8]> {
-        println!("{:?}", &md5::compute([]).0);
-        println!("{:?}", &md5::compute([]).0);
-        let key = &md5::compute([]).0;
-        let iv = &md5::compute([]).0;
-
-        let buf: &mut Vec<u8> = match data {
-            // We can use unsafe here because we don't use the string as a string after
-            EncryptArgs::String(s) => unsafe { s.as_mut_vec() } ,
-            EncryptArgs::Bytes( b) => b
-        };
-
-        let len = buf.len();
-        buf.resize(len + 16 - (len % 16), 0);
-        let encrypted = AES_CBC_encryptor::new(key.into(), iv.into())
-            .encrypt_padded_mut::<Pkcs7>(buf, len).unwrap();
-        Ok(encrypted)
-    }
     async fn fetch_session_id(&mut self) -> Result<()> {
-        let eleve_html = self.client.get("https://demo.index-education.net/pronote/eleve.html")
+        let page = self.client.get(self.instance.page_url())
             .header("User-Agent", NORMAL_USER_AGENT)
             .send().await?;
-        let eleve_html = eleve_html.text().await?;
-        let session_id = &eleve_html.split_once("h:'").ok_or(anyhow!("Invalide eleve.html : {}", eleve_html))?.1[..7];
+        let page = page.text().await?;
+        let params = parse_start_params(&page)?;
+        let session_id = params.get("h").ok_or(anyhow!("Invalid page of the espace : {}", page))?;
         self.session_id = session_id.parse().with_context(|| format!("session_id = {}", session_id))?;
+        // `a` is the espace of the page, the server may have redirected to another one
+        if let Some(espace) = params.get("a").filter(|a| **a != self.instance.espace.genre().to_string()) {
+            return Err(anyhow!("The page {} is of the espace {} instead of {}", self.instance.page_url(), espace, self.instance.espace.genre()));
+        }
+        self.http = params.get("http").is_some_and(|http| http == "true");
+        self.security = Security::from_params(&params);
         Ok(())
     }
-    async fn send_request<N: Display, D: Display>(&self, name: N, data: D) -> Result<reqwest::Response> {
-        let mut numero_ordre = self.numero_ordre.fetch_add(1, Ordering::SeqCst).to_string().into();
-        let numero_ordre = self.encrypt(&mut numero_ordre)?;
-        let numero_ordre = hex::encode(numero_ordre);
+    /// Send the request, returns its counter and the response as received
+    async fn post_request<R: Request>(&self, request: &R) -> Result<(u32, Message)> {
+        // The responses take the numbers between the requests
+        let counter = self.numero_ordre.fetch_add(2, Ordering::SeqCst);
+        let numero_ordre = self.crypto.numero_ordre(counter);
 
-        let body = format!(r#"{{
-            "session": {},
-            "numeroOrdre": "{}",
-            "nom": "{}",
-            "donneesSec": {{
-                "donnees": {{
-                    {}
-                }}
-            }}
-        }}"#, self.session_id, numero_ordre, name, data);
-        println!("body = {}", body);
+        // Requests without parameters, like ParametresUtilisateur, have no donnees
+        let donnees = serde_json::to_value(request)?;
+        let donnees_sec = DonneesSec {
+            donnees: (!donnees.is_null()).then_some(donnees),
+            nom: None,
+            signature: request.onglet().map(|onglet| json!({ "onglet": onglet }))
+        };
+        let body = serde_json::to_string(&Message {
+            session: self.session_id,
+            numero_ordre: numero_ordre.clone(),
+            nom: R::NAME.to_string(),
+            donnees_sec: messages::seal(&self.crypto, self.security, serde_json::to_value(donnees_sec)?)?,
+            erreur: None
+        })?;
 
-        let res = self.client.post(format!("https://demo.index-education.net/pronote/appelfonction/3/{}/{}", self.session_id, numero_ordre))
+        let res = self.client.post(self.instance.appelfonction_url(self.session_id, &numero_ordre))
             .header("User-Agent", NORMAL_USER_AGENT)
             .header("Content-Type", "application/json")
             .header("Content-Length", body.len())
             .header("Accept", "*/*")
             .body(body)
             .send().await?;
-        Ok(res)
+        Ok((counter, res.json::<Message>().await?))
+    }
+    /// `donnees` of the response to `R`, decrypted and checked
+    fn open_response<R: Request>(&self, counter: u32, res: Message) -> Result<R::Response> {
+        let donnees_sec = messages::open(&self.crypto, self.security, self.session_id, counter, res)
+            .with_context(|| format!("Invalid response to {}", R::NAME))?;
+        let donnees_sec = serde_json::from_value::<DonneesSec<R::Response>>(donnees_sec)
+            .with_context(|| format!("Unexpected response to {}", R::NAME))?;
+        donnees_sec.donnees.ok_or(anyhow!("No donnees in the response to {}", R::NAME))
+    }
+    async fn send_request<R: Request>(&self, request: &R) -> Result<R::Response> {
+        let (counter, res) = self.post_request(request).await?;
+        self.open_response::<R>(counter, res)
     }
     pub async fn connect(&mut self) -> Result<()> {
         self.fetch_session_id().await?;
-        println!("session_id = {}", self.session_id);
-
-        let base64_iv = base64::encode(&self.iv_aes);
-
-        let res = self.send_request("FonctionParametres", 
-            format!(
-                r#""Uuid": "{}",
-                "identifiantNav": """#,
-                base64_iv
-            )
-        ).await?;
 
-        println!("res = {:#?}", res);
-        println!("res.text = {:#?}", res.text().await?);
-
-        let res = self.send_request("Identification",
-            r#""genreConnexion": 0,
-            "genreEspace": 3,
-            "identifiant": "demonstration",
-            "pourENT": false,
-            "enConnexionAuto": false,
-            "demandeConnexionAuto": false,
-            "demandeConnexionAppliMobile": false,
-            "demandeConnexionAppliMobileJeton": false,
-            "uuidAppliMobile": "",
-            "loginTokenSAV": """#
-        ).await?;
-
-        println!("res = {:#?}", res);
-        println!("res.text = {:#?}", res.text().await?);
+        let request = FonctionParametres {
+            uuid: self.crypto.serialize_iv(if self.http { Some(RSA_1024) } else { None })?,
+            identifiant_nav: String::new()
+        };
+        let (counter, res) = self.post_request(&request).await?;
+        // The response and the next messages use the IV sent in the request
+        self.crypto.switch_iv();
+        self.open_response::<FonctionParametres>(counter, res)?;
+        Ok(())
+    }
+    /// Log in with a password, after `connect`.
+    ///
+    /// The password is never sent: the server checks the user can decrypt its challenge with it, then sends the key of the session encrypted with it
+    pub async fn login(&mut self, identifiant: &str, password: &str) -> Result<(), LoginError> {
+        let identification = self.send_request(&Identification::new(identifiant.to_string(), self.instance.espace.genre())).await?;
+        let user_key = login::user_key(identifiant, password, &identification);
+        // Only the right password decrypts the challenge
+        let solved = self.crypto.solve_challenge(&user_key, &identification.challenge)
+            .map_err(|e| if e.is::<WrongKey>() { LoginError::BadCredentials } else { LoginError::Other(e) })?;
 
+        let res = self.send_request(&Authentification { connexion: 0, challenge: solved, espace: self.instance.espace.genre() }).await?;
+        let cle = match &res.cle {
+            None => return Err(login::refusal(&res)),
+            Some(cle) => cle
+        };
+        let key = self.crypto.session_key(&user_key, cle)?;
+        self.crypto.set_key(&key);
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_params() {
+        let page = include_str!("../SITE DE DEMONSTRATION - PRONOTE - Espace Élèves - Page d'accueil.html");
+        let params = parse_start_params(page).unwrap();
+        assert_eq!(params.get("h").map(|h| h.as_str()), Some("9435671"));
+        assert_eq!(params.get("sCrA").map(|s| s.as_str()), Some("true"));
+        assert!(!params.contains_key("http"));
+        let params = parse_start_params("Start ({h:'12',http:true,a:3})").unwrap();
+        assert_eq!(params.get("http").map(|h| h.as_str()), Some("true"));
+        assert!(parse_start_params("<html></html>").is_err());
+    }
+}
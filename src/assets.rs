@@ -0,0 +1,256 @@
+use std::{collections::HashMap, path::PathBuf, sync::{Arc, RwLock}, time::{SystemTime, UNIX_EPOCH}};
+use anyhow::{Context, Result};
+use minijinja::Environment;
+use http_bytes::{http, http::StatusCode};
+use walkdir::WalkDir;
+use crate::scss;
+
+/// A file from `public/` served as is
+#[derive(Clone)]
+pub struct StaticFile {
+    pub content: Vec<u8>,
+    pub content_type: &'static str,
+    pub etag: String,
+    pub last_modified: String
+}
+impl StaticFile {
+    pub fn new(content: Vec<u8>, content_type: &'static str, modified: i64) -> Self {
+        let etag = format!("\"{:x}\"", md5::compute(&content));
+        let last_modified = time::strftime("%a, %d %b %Y %H:%M:%S GMT", &time::at_utc(time::Timespec::new(modified, 0))).unwrap();
+        Self {
+            content,
+            content_type,
+            etag,
+            last_modified
+        }
+    }
+
+    /// Build the response for this file, or a 304 if the client already has it
+    pub fn response<T>(&self, req: &http::Request<T>) -> http::Response<Vec<u8>> {
+        let cached = req.headers().get_all(http::header::IF_NONE_MATCH).iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == self.etag);
+
+        let mut builder = http::Response::builder();
+        builder
+            .header(http::header::ETAG, self.etag.as_str())
+            .header(http::header::LAST_MODIFIED, self.last_modified.as_str())
+            .header(http::header::CACHE_CONTROL, "no-cache");
+        if cached {
+            builder.status(StatusCode::NOT_MODIFIED).body(Vec::new()).unwrap()
+        } else {
+            builder
+                .header(http::header::CONTENT_TYPE, self.content_type)
+                .body(self.content.clone())
+                .unwrap()
+        }
+    }
+}
+
+/// Everything loaded from `public/`
+#[derive(Clone)]
+pub struct Assets {
+    /// Templates (`.j2` files). Text files are also added so that they can be included by templates
+    pub env: Environment<'static>,
+    /// Files that are not templates, by their url path
+    pub files: HashMap<String, StaticFile>,
+    /// Errors from compiling the templates and the stylesheets, shown on the pages in dev mode
+    pub errors: Vec<String>
+}
+impl Assets {
+    /// Load the files of `public/`.
+    ///
+    /// When reloading, the templates and stylesheets that don't compile anymore are taken from `previous`, the last assets that were served.
+    /// Files deleted during the walk are skipped.
+    pub async fn load(previous: Option<&Assets>) -> Result<Self> {
+        let mut env = Environment::new();
+        let mut files = HashMap::new();
+        let mut errors = Vec::new();
+        let mut stylesheets = Vec::new();
+        let mut scss_stamp = UNIX_EPOCH;
+        let template_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("public");
+        for entry in WalkDir::new(&template_path) {
+            let entry = match entry {
+                Err(e) if e.depth() > 0 && is_vanished(e.io_error()) => continue,
+                entry => entry.with_context(|| format!("Can't list {}", template_path.display()))?
+            };
+            if entry.file_type().is_file() {
+                let path = entry.path();
+                if path.file_name().is_some_and(|name| name == ".DS_Store") {
+                    continue;
+                }
+                let modified = match entry.metadata().map_err(std::io::Error::from).and_then(|m| m.modified()) {
+                    Err(e) if is_vanished(Some(&e)) => continue,
+                    modified => modified.with_context(|| format!("Can't read {}", path.display()))?
+                };
+                let url_path = path.strip_prefix(&template_path)?;
+                let url_path = "/".to_string() + url_path.to_str().with_context(|| format!("Invalid file name {}", path.display()))?;
+                if let Some(css_path) = url_path.strip_suffix(".scss") {
+                    // Compiled after the walk, once we know when the last SCSS file changed
+                    scss_stamp = scss_stamp.max(modified);
+                    if !entry.file_name().to_string_lossy().starts_with('_') {
+                        stylesheets.push((path.to_owned(), css_path.to_string() + ".css"));
+                    }
+                    continue;
+                }
+                let content = match std::fs::read(path) {
+                    Err(e) if is_vanished(Some(&e)) => continue,
+                    content => content.with_context(|| format!("Can't read {}", path.display()))?
+                };
+                if let Some(url_path) = url_path.strip_suffix(".j2") {
+                    match String::from_utf8(content) {
+                        Ok(source) => add_template(&mut env, &mut errors, previous, url_path.to_owned(), source),
+                        Err(_) => errors.push(format!("{} : not in UTF-8", url_path))
+                    }
+                    continue;
+                }
+                let content_type = content_type(&url_path);
+                if content_type.starts_with("text/") || content_type == "image/svg+xml" {
+                    if let Ok(text) = std::str::from_utf8(&content) {
+                        add_template(&mut env, &mut errors, previous, url_path.clone(), text.to_owned());
+                    }
+                }
+                files.insert(url_path, StaticFile::new(content, content_type, unix_secs(modified)));
+            }
+        }
+
+        for (path, css_path) in stylesheets {
+            match scss::compile(&path, scss_stamp) {
+                Ok(css) => {
+                    add_template(&mut env, &mut errors, previous, css_path.clone(), css.clone());
+                    files.insert(css_path.clone(), StaticFile::new(css.into_bytes(), content_type(&css_path), unix_secs(scss_stamp)));
+                },
+                Err(e) => {
+                    eprintln!("Error compiling {} : {}", path.display(), e);
+                    errors.push(e);
+                    // Keep the pages that include the stylesheet working, with its last version if there is one
+                    match previous.and_then(|p| Some((p.env.get_template(&css_path).ok()?, p.files.get(&css_path)?))) {
+                        Some((template, file)) => {
+                            add_template(&mut env, &mut errors, previous, css_path.clone(), template.source().to_owned());
+                            files.insert(css_path, file.clone());
+                        }
+                        None => add_template(&mut env, &mut errors, None, css_path, String::new())
+                    }
+                }
+            }
+        }
+
+        Ok(Self { env, files, errors })
+    }
+}
+
+/// Add a template, or record its error and keep its version from the `previous` assets so the pages using it still work
+fn add_template(env: &mut Environment<'static>, errors: &mut Vec<String>, previous: Option<&Assets>, name: String, source: String) {
+    if let Err(e) = env.add_template_owned(name.clone(), source) {
+        eprintln!("Error compiling {} : {}", name, e);
+        errors.push(format!("{} : {:#}", name, e));
+        if let Some(template) = previous.and_then(|p| p.env.get_template(&name).ok()) {
+            env.add_template_owned(name, template.source().to_owned()).expect("The previous version compiled");
+        }
+    }
+}
+
+/// Whether a file disappeared while loading the assets, ex: the temporary files of the editors
+fn is_vanished(error: Option<&std::io::Error>) -> bool {
+    error.is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// The current assets, replaced when the files change in dev mode
+pub struct SharedAssets(RwLock<Arc<Assets>>);
+impl SharedAssets {
+    pub fn new(assets: Assets) -> Self {
+        Self(RwLock::new(Arc::new(assets)))
+    }
+    pub fn get(&self) -> Arc<Assets> {
+        self.0.read().unwrap().clone()
+    }
+    pub fn set(&self, assets: Assets) {
+        *self.0.write().unwrap() = Arc::new(assets);
+    }
+}
+
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+/// Guess the MIME type from the extension of `path`
+pub fn content_type(path: &str) -> &'static str {
+    let ext = match path.rsplit_once('.') {
+        None => "",
+        Some((_, ext)) => ext
+    };
+    match ext.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        _ => "application/octet-stream"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_types() {
+        assert_eq!(content_type("/styles/index.css"), "text/css; charset=utf-8");
+        assert_eq!(content_type("/img/Logo.PNG"), "image/png");
+        assert_eq!(content_type("/img/icon.svg"), "image/svg+xml");
+        assert_eq!(content_type("/fonts/rubik.woff2"), "font/woff2");
+        assert_eq!(content_type("/LICENSE"), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn load() {
+        let assets = Assets::load(None).await.unwrap();
+        assert!(assets.errors.is_empty(), "{:?}", assets.errors);
+        // The templates are rendered, the other files are served as is and can be included
+        assert!(assets.env.get_template("/index.html").is_ok());
+        assert!(!assets.files.contains_key("/index.html.j2") && !assets.files.contains_key("/index.html"));
+        assert_eq!(assets.files["/img/small_logo.svg"].content_type, "image/svg+xml");
+        assert!(assets.env.get_template("/img/small_logo.svg").is_ok());
+        // The stylesheets are compiled
+        assert!(!assets.files.contains_key("/styles/index.scss"));
+        assert!(assets.env.get_template("/styles/index.css").is_ok());
+    }
+
+    #[test]
+    fn etags() {
+        let file = StaticFile::new(b"body { color: red; }".to_vec(), "text/css; charset=utf-8", 1_700_000_000);
+        let request = |if_none_match: Option<&str>| {
+            let mut builder = http::Request::builder();
+            if let Some(tag) = if_none_match {
+                builder.header(http::header::IF_NONE_MATCH, tag);
+            }
+            builder.body(()).unwrap()
+        };
+
+        let res = file.response(&request(None));
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body(), &file.content);
+        assert_eq!(res.headers()[http::header::ETAG], file.etag.as_str());
+        assert_eq!(res.headers()[http::header::LAST_MODIFIED], "Tue, 14 Nov 2023 22:13:20 GMT");
+        assert_eq!(res.headers()[http::header::CONTENT_TYPE], "text/css; charset=utf-8");
+
+        for tag in [file.etag.clone(), format!("W/{}", file.etag), format!("\"other\", {}", file.etag), "*".to_string()] {
+            let res = file.response(&request(Some(&tag)));
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED, "{}", tag);
+            assert!(res.body().is_empty());
+            assert_eq!(res.headers()[http::header::ETAG], file.etag.as_str());
+        }
+        assert_eq!(file.response(&request(Some("\"other\""))).status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use crate::{dates, db::{ClassFilter, DB, HolidayFilter, LessonData, LessonFilter}};
+
+/// What an overlapping lesson has in common with the lesson being saved
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictKind {
+    Class,
+    Room,
+    Teacher
+}
+impl ConflictKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Class => "class",
+            Self::Room => "room",
+            Self::Teacher => "teacher"
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Class => "Classe",
+            Self::Room => "Salle",
+            Self::Teacher => "Professeur"
+        }
+    }
+}
+
+/// Classes, rooms and teachers of a lesson, which can't be in two lessons at the same time
+#[derive(Clone, Debug, Default)]
+pub struct Resources {
+    pub class_ids: Vec<usize>,
+    pub room_ids: Vec<usize>,
+    pub teacher_ids: Vec<usize>
+}
+
+/// An occurrence of another lesson at the same time as the lesson being saved
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    /// Name of the class, room or teacher of both lessons
+    pub name: String,
+    pub lesson_id: usize,
+    /// Lesson type of the other lesson
+    pub lesson_name: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime
+}
+impl Conflict {
+    /// Description shown to the user, and recorded when an admin overrides the conflict
+    pub fn describe(&self) -> String {
+        format!("{} {} : {} le {}", self.kind.label(), self.name, self.lesson_name, dates::format_datetime(self.start))
+    }
+}
+
+/// Conflicts of a lesson with the other lessons, checked before creating or updating it.
+///
+/// Two lessons conflict when some of their occurrences overlap and they have a class, a room or a teacher in common.
+/// When updating a lesson, `lesson_id` is its id so it doesn't conflict with itself.
+pub async fn find(db: &DB, lesson_id: Option<usize>, data: &LessonData, resources: &Resources) -> Result<Vec<Conflict>> {
+    let holidays = db.list_holidays(HolidayFilter::default()).await?;
+    let planned = data.occurrences(&holidays);
+    let (from, to) = match (planned.first(), planned.last()) {
+        (Some(first), Some(last)) => (first.0, last.1),
+        _ => return Ok(Vec::new())
+    };
+    let others = db.list_occurrences(LessonFilter { from: Some(from), to: Some(to), ..Default::default() }).await?
+        .into_iter()
+        .filter(|o| Some(o.lesson.id) != lesson_id && !o.cancelled)
+        .filter(|o| planned.iter().any(|(start, end)| *start < o.end && o.start < *end))
+        .collect::<Vec<_>>();
+    if others.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lesson_ids = others.iter().map(|o| o.lesson.id).collect::<Vec<_>>();
+    lesson_ids.sort();
+    lesson_ids.dedup();
+    let mut lesson_classes: HashMap<usize, Vec<usize>> = HashMap::new();
+    for lesson_class in db.list_classes_of_lessons(lesson_ids.clone()).await? {
+        lesson_classes.entry(lesson_class.lesson_id).or_default().push(lesson_class.class_id);
+    }
+    let mut lesson_rooms: HashMap<usize, Vec<usize>> = HashMap::new();
+    for lesson_room in db.list_rooms_of_lessons(lesson_ids.clone()).await? {
+        lesson_rooms.entry(lesson_room.lesson_id).or_default().push(lesson_room.room_id);
+    }
+    let mut lesson_teachers: HashMap<usize, Vec<usize>> = HashMap::new();
+    for lesson_teacher in db.list_teachers_of_lessons(lesson_ids).await? {
+        lesson_teachers.entry(lesson_teacher.lesson_id).or_default().push(lesson_teacher.teacher_id);
+    }
+
+    let class_names = db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect::<HashMap<_, _>>();
+    let room_names = db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| (r.id, r.name))
+        .collect::<HashMap<_, _>>();
+    let teacher_names = db.list_teachers(false).await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect::<HashMap<_, _>>();
+    let type_names = db.list_lesson_types(false).await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect::<HashMap<_, _>>();
+
+    let mut conflicts = Vec::new();
+    for other in others {
+        let conflict = |kind, name: String| Conflict {
+            kind,
+            name,
+            lesson_id: other.lesson.id,
+            lesson_name: type_names.get(&other.lesson.type_id).cloned().unwrap_or_default(),
+            start: other.start,
+            end: other.end
+        };
+        let other_rooms = match other.room_id {
+            None => lesson_rooms.get(&other.lesson.id).cloned().unwrap_or_default(),
+            Some(room_id) => vec![room_id]
+        };
+        for (kind, ids, other_ids, names) in [
+            (ConflictKind::Class, &resources.class_ids, lesson_classes.get(&other.lesson.id).cloned().unwrap_or_default(), &class_names),
+            (ConflictKind::Room, &resources.room_ids, other_rooms, &room_names),
+            (ConflictKind::Teacher, &resources.teacher_ids, lesson_teachers.get(&other.lesson.id).cloned().unwrap_or_default(), &teacher_names)
+        ] {
+            for id in ids.iter().filter(|id| other_ids.contains(id)) {
+                conflicts.push(conflict(kind, names.get(id).cloned().unwrap_or_default()));
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::db::{LessonException, Repeat};
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+    fn data(type_id: usize, start: NaiveDateTime) -> LessonData {
+        LessonData { type_id, start, end: start + chrono::Duration::hours(1), repeat: None }
+    }
+    fn resources(class_ids: &[usize], room_ids: &[usize], teacher_ids: &[usize]) -> Resources {
+        Resources { class_ids: class_ids.to_vec(), room_ids: room_ids.to_vec(), teacher_ids: teacher_ids.to_vec() }
+    }
+
+    #[tokio::test]
+    async fn conflicts() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+        let a01 = db.insert_room("A01".to_string()).await.unwrap();
+        let b12 = db.insert_room("B12".to_string()).await.unwrap();
+        let dupont = db.insert_teacher("M. Dupont".to_string(), None).await.unwrap();
+        let martin = db.insert_teacher("Mme Martin".to_string(), None).await.unwrap();
+        // Every monday at 8
+        let weekly = db.insert_lesson(LessonData {
+            repeat: Some(Repeat { weeks: 1, until: at(25, 8).date() }),
+            ..data(maths, at(4, 8))
+        }).await.unwrap();
+        db.set_lesson_classes(weekly, vec![class]).await.unwrap();
+        db.set_lesson_rooms(weekly, vec![a01]).await.unwrap();
+        db.set_lesson_teachers(weekly, vec![dupont, martin]).await.unwrap();
+
+        let kinds = |conflicts: Vec<Conflict>| conflicts.iter().map(|c| (c.kind, c.name.clone(), c.start)).collect::<Vec<_>>();
+        let conflicts = find(&db, None, &data(maths, at(18, 8)), &resources(&[class, other], &[a01], &[])).await.unwrap();
+        assert_eq!(kinds(conflicts), [(ConflictKind::Class, "1A".to_string(), at(18, 8)), (ConflictKind::Room, "A01".to_string(), at(18, 8))]);
+        let conflicts = find(&db, None, &data(maths, at(11, 8)), &resources(&[other], &[b12], &[martin])).await.unwrap();
+        assert_eq!(kinds(conflicts), [(ConflictKind::Teacher, "Mme Martin".to_string(), at(11, 8))]);
+        // Not at the same time, after the end of the lesson, or the lesson itself
+        let all = resources(&[class], &[a01], &[dupont]);
+        assert!(find(&db, None, &data(maths, at(4, 9)), &all).await.unwrap().is_empty());
+        assert!(find(&db, None, &data(maths, at(2, 8) + chrono::Duration::days(30)), &all).await.unwrap().is_empty());
+        assert!(find(&db, Some(weekly), &data(maths, at(4, 8)), &all).await.unwrap().is_empty());
+
+        // An occurrence moved to another room
+        db.set_lesson_exception(LessonException { lesson_id: weekly, date: at(11, 8).date(), cancelled: false, moved_to: None, room_id: Some(b12) }).await.unwrap();
+        let conflicts = find(&db, None, &data(maths, at(11, 8)), &resources(&[], &[a01, b12], &[])).await.unwrap();
+        assert_eq!(kinds(conflicts), [(ConflictKind::Room, "B12".to_string(), at(11, 8))]);
+    }
+}
@@ -0,0 +1,38 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+const MONTHS: [&str; 12] = [
+    "janvier", "février", "mars", "avril", "mai", "juin",
+    "juillet", "août", "septembre", "octobre", "novembre", "décembre"
+];
+
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "lundi",
+        Weekday::Tue => "mardi",
+        Weekday::Wed => "mercredi",
+        Weekday::Thu => "jeudi",
+        Weekday::Fri => "vendredi",
+        Weekday::Sat => "samedi",
+        Weekday::Sun => "dimanche"
+    }
+}
+
+/// ex: "lundi 4 septembre"
+pub fn format_day(date: NaiveDate) -> String {
+    format!("{} {} {}", weekday_name(date.weekday()), date.day(), MONTHS[date.month0() as usize])
+}
+
+/// ex: "lundi 4 septembre à 08:00"
+pub fn format_datetime(datetime: NaiveDateTime) -> String {
+    format!("{} à {}", format_day(datetime.date()), datetime.format("%H:%M"))
+}
+
+/// Parse a date from an `<input type="date">`
+pub fn parse_day(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// Parse a time from an `<input type="time">`
+pub fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").ok()
+}
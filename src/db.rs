@@ -1,168 +1,288 @@
-use rusqlite::OptionalExtension;
+use rusqlite::{Row, ToSql, types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Value, ValueRef}};
 use tokio_rusqlite::Connection;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use anyhow::Result;
+use crate::migrations;
 
-pub struct Class {
-    pub id: usize,
-    pub name: String,
-    pub created_at: NaiveDate,
-    pub deleted_at: Option<NaiveDate>
+mod classes;
+mod holidays;
+mod homeworks;
+mod lessons;
+mod membership_changes;
+mod memberships;
+mod occurrences;
+mod overrides;
+mod rooms;
+mod sessions;
+mod teachers;
+mod trash;
+mod users;
+
+pub use self::{
+    classes::{Class, ClassFilter},
+    holidays::{Holiday, HolidayFilter},
+    homeworks::{Homework, HomeworkData, HomeworkFilter},
+    lessons::{Lesson, LessonData, LessonFilter, Repeat},
+    membership_changes::{MembershipAction, MembershipChange},
+    memberships::{Membership, MembershipFilter},
+    occurrences::{LessonException, Occurrence},
+    trash::{TrashItem, TrashKind},
+    users::{User, UserFilter}
+};
+
+/// Role of a user on the whole site (student, teacher or admin) or in a class (student, delegate or teacher)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Student,
+    Delegate,
+    Teacher,
+    Admin
+}
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Student => "student",
+            Self::Delegate => "delegate",
+            Self::Teacher => "teacher",
+            Self::Admin => "admin"
+        }
+    }
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "student" => Some(Self::Student),
+            "delegate" => Some(Self::Delegate),
+            "teacher" => Some(Self::Teacher),
+            "admin" => Some(Self::Admin),
+            _ => None
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Student => "Élève",
+            Self::Delegate => "Délégué",
+            Self::Teacher => "Professeur",
+            Self::Admin => "Administrateur"
+        }
+    }
 }
-impl Class {
-    pub fn is_deleted(&self) -> bool {
-        self.deleted_at.is_some()
+impl ToSql for Role {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+impl FromSql for Role {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Self::parse(value.as_str()?).ok_or(FromSqlError::InvalidType)
+    }
+}
+
+/// Conditions of a `WHERE` clause, with the values of their `?` parameters in order
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    params: Vec<Value>
+}
+impl Conditions {
+    /// Add a condition with one parameter (ex: `class_id = ?`)
+    fn add(&mut self, clause: &str, value: Value) {
+        self.clauses.push(clause.to_string());
+        self.params.push(value);
+    }
+    /// Add a condition with several parameters
+    fn add_many(&mut self, clause: &str, values: Vec<Value>) {
+        self.clauses.push(clause.to_string());
+        self.params.extend(values);
+    }
+    /// Add a condition without parameters
+    fn add_clause(&mut self, clause: &str) {
+        self.clauses.push(clause.to_string());
+    }
+    /// Add a condition if the filter is set
+    fn add_opt<T>(&mut self, clause: &str, value: Option<T>, to_value: fn(T) -> Value) {
+        if let Some(value) = value {
+            self.add(clause, to_value(value));
+        }
+    }
+    /// Add a condition on a list of values, `{}` in `clause` is replaced by their parameters (ex: `class_id IN ({})`)
+    fn add_in(&mut self, clause: &str, values: Vec<Value>) {
+        let placeholders = vec!["?"; values.len()].join(", ");
+        self.clauses.push(clause.replace("{}", &placeholders));
+        self.params.extend(values);
+    }
+    /// Exclude the soft deleted rows unless `include_deleted`
+    fn not_deleted(&mut self, include_deleted: bool) {
+        if !include_deleted {
+            self.add_clause("deleted_at IS NULL");
+        }
+    }
+    fn sql(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.clauses.join(" AND "))
+        }
+    }
+}
+
+/// Whether the query failed because of a UNIQUE constraint, ex: a name already taken
+pub fn is_unique_violation(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<tokio_rusqlite::Error>() {
+        Some(tokio_rusqlite::Error::Rusqlite(rusqlite::Error::SqliteFailure(e, _))) => e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE,
+        _ => false
     }
 }
 
-pub struct User {
-    pub id: usize,
-    pub name: String,
-    pub password_hash: String,
-    pub created_at: NaiveDate,
-    pub deleted_at: Option<NaiveDate>
+/// Parameter value of an id
+fn id(id: usize) -> Value {
+    Value::Integer(id as i64)
+}
+/// Parameter value of a date, in the format used by rusqlite so they can be compared
+fn datetime(datetime: NaiveDateTime) -> Value {
+    Value::Text(datetime.format("%F %T%.f").to_string())
+}
+/// Parameter value of a day, in the format used by rusqlite
+fn date(date: NaiveDate) -> Value {
+    Value::Text(date.format("%F").to_string())
+}
+fn text(text: String) -> Value {
+    Value::Text(text)
+}
+fn role(role: Role) -> Value {
+    Value::Text(role.as_str().to_string())
 }
 
 pub struct DB {
     pub conn: Connection,
 }
 impl DB {
-    pub async fn new(path: Option<&str>) -> Self {
+    pub async fn new(path: Option<&str>) -> Result<Self> {
         let conn = match path {
-            Some(path) => Connection::open(path).await.expect("Failed to open database"),
-            None => Connection::open_in_memory().await.expect("Failed to open database")
+            Some(path) => Connection::open(path).await?,
+            None => Connection::open_in_memory().await?
         };
-        let db = Self { conn };
-        db.create_tables().await;
-        db
-    }
-    pub async fn create_tables(&self) {
-        self.conn.call(|conn| {
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS classes (
-                    id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL UNIQUE,
-                    created_at TEXT NOT NULL,
-                    deleted_at TEXT
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS users (
-                    id INTEGER PRIMARY KEY,
-                    username TEXT NOT NULL,
-                    password_hash TEXT NOT NULL,
-                    created_at TEXT NOT NULL,
-                    deleted_at TEXT
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS user_classes (
-                    user_id INTEGER NOT NULL,
-                    class_id INTEGER NOT NULL,
-                    FOREIGN KEY (user_id) REFERENCES users (id),
-                    FOREIGN KEY (class_id) REFERENCES classes (id)
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS homeworks (
-                    id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL,
-                    description TEXT NOT NULL,
-                    class_id INTEGER NOT NULL,
-                    created_by INTEGER,
-                    lesson_id INTEGER,
-                    created_at TEXT NOT NULL,
-                    deleted_at TEXT,
-                    FOREIGN KEY (class_id) REFERENCES classes (id),
-                    FOREIGN KEY (created_by) REFERENCES users (id)
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS lesson_types (
-                    id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL UNIQUE,
-                    created_at TEXT NOT NULL,
-                    deleted_at TEXT
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS lessons (
-                    id INTEGER PRIMARY KEY,
-                    type_id INTEGER NOT NULL,
-                    start TEXT NOT NULL,
-                    end TEXT NOT NULL,
-                    created_at TEXT NOT NULL,
-                    deleted_at TEXT,
-                    FOREIGN KEY (type_id) REFERENCES lesson_types (id)
-                )
-            ", ())?;
-            conn.execute("
-                CREATE TABLE IF NOT EXISTS lesson_classes (
-                    lesson_id INTEGER NOT NULL,
-                    class_id INTEGER NOT NULL,
-                    FOREIGN KEY (lesson_id) REFERENCES lessons (id),
-                    FOREIGN KEY (class_id) REFERENCES classes (id)
-                )
-            ", ())?;
+        migrations::migrate(&conn).await?;
+        Ok(Self { conn })
+    }
 
-            Ok(())
-        }).await.expect("Failed to create tables");
-    }
-    
-    // classes
-    pub async fn insert_class(&self, name: String) -> Result<()> {
-        self.conn.call(|conn| {
-            conn.execute("
-                INSERT INTO classes (name, created_at)
-                VALUES (?1, datetime('now'))
-            ", [name])?;
-            Ok(())
+    /// Run a `SELECT` and convert each row with `from_row`
+    async fn select<T: Send + 'static>(&self, sql: String, params: Vec<Value>, from_row: fn(&Row) -> rusqlite::Result<T>) -> Result<Vec<T>> {
+        let rows = self.conn.call(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(rows)
         }).await?;
-        Ok(())
+        Ok(rows)
     }
-    pub async fn get_class(&self, id: usize) -> Option<Class> {
-        self.conn.call(move |conn| {
-            conn.query_row("
-                SELECT id, name, created_at, deleted_at
-                FROM classes
-                WHERE id = ?1
-            ", [id], |row| {
-                Ok(Class {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    created_at: row.get(2)?,
-                    deleted_at: row.get(3)?
-                })
-            }).optional()
-        }).await.expect("Failed to get class")
+    /// Run a `SELECT` returning at most one row
+    async fn select_one<T: Send + 'static>(&self, sql: String, params: Vec<Value>, from_row: fn(&Row) -> rusqlite::Result<T>) -> Result<Option<T>> {
+        Ok(self.select(sql, params, from_row).await?.into_iter().next())
+    }
+    /// Run an `INSERT`, `UPDATE` or `DELETE` and return the number of rows changed
+    async fn execute(&self, sql: String, params: Vec<Value>) -> Result<usize> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute(&sql, rusqlite::params_from_iter(params))
+        }).await?;
+        Ok(changed)
     }
 
-    // users
-    pub async fn insert_user(&self, name: String, password_hash: String) -> Result<()> {
-        self.conn.call(|conn| {
-            conn.execute("
-                INSERT INTO users (name, password_hash, created_at)
-                VALUES (?1, ?2, datetime('now'))
-            ", [name, password_hash])?;
-            Ok(())
+    /// Soft delete the rows of `table` matching `condition`, and the rows depending on them (see `CASCADES`).
+    ///
+    /// Returns false if there was nothing to delete
+    async fn soft_delete(&self, table: &'static str, condition: &str, params: Vec<Value>) -> Result<bool> {
+        // Everything deleted together has the same date, so it can be restored together
+        let now = datetime(Utc::now().naive_utc());
+        let sql = format!("
+            UPDATE {}
+            SET deleted_at = ?
+            WHERE ({}) AND deleted_at IS NULL
+        ", table, condition);
+        let changed = self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            let changed = tx.execute(&sql, rusqlite::params_from_iter(std::iter::once(now.clone()).chain(params)))?;
+            cascade_delete(&tx, table, &now)?;
+            tx.commit()?;
+            Ok(changed)
         }).await?;
-        Ok(())
+        Ok(changed > 0)
     }
-    pub async fn get_user(&self, id: usize) -> Option<User> {
+    /// Make `ids` the rows linked to the lesson in a link table (ex: the rooms of a lesson in `lesson_rooms`).
+    ///
+    /// The links removed are soft deleted, and the ones added again are restored
+    async fn set_lesson_links(&self, table: &'static str, column: &'static str, lesson_id: usize, ids: Vec<usize>) -> Result<()> {
+        let now = datetime(Utc::now().naive_utc());
         self.conn.call(move |conn| {
-            conn.query_row("
-                SELECT id, name, password_hash, created_at, deleted_at
-                FROM classes
-                WHERE id = ?1
-            ", [id], |row| {
-                Ok(User {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    password_hash: row.get(2)?,
-                    created_at: row.get(3)?,
-                    deleted_at: row.get(4)?
-                })
-            }).optional()
-        }).await.expect("Failed to get user")
+            let tx = conn.transaction()?;
+            let placeholders = vec!["?"; ids.len()].join(", ");
+            tx.execute(
+                &format!("UPDATE {table} SET deleted_at = ? WHERE lesson_id = ? AND {column} NOT IN ({placeholders}) AND deleted_at IS NULL"),
+                rusqlite::params_from_iter([now, id(lesson_id)].into_iter().chain(ids.iter().map(|i| id(*i))))
+            )?;
+            for link_id in ids {
+                tx.execute(&format!("
+                    INSERT INTO {table} (lesson_id, {column})
+                    VALUES (?1, ?2)
+                    ON CONFLICT (lesson_id, {column}) DO UPDATE SET deleted_at = NULL
+                "), [lesson_id, link_id])?;
+            }
+            tx.commit()
+        }).await?;
+        Ok(())
+    }
+    /// Restore the soft deleted rows of `table` matching `condition`, and the rows deleted with them.
+    ///
+    /// Returns false if there was nothing to restore
+    async fn restore(&self, table: &'static str, condition: &str, params: Vec<Value>) -> Result<bool> {
+        let condition = condition.to_string();
+        let changed = self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            let changed = cascade_restore(&tx, table, &condition, &params)?;
+            tx.commit()?;
+            Ok(changed)
+        }).await?;
+        Ok(changed > 0)
+    }
+}
+
+/// Rows hidden when the row they depend on is deleted : (table, dependent table, column of the dependent table referencing the table)
+const CASCADES: &[(&str, &str, &str)] = &[
+    ("classes", "homeworks", "class_id"),
+    ("classes", "user_classes", "class_id"),
+    ("classes", "lesson_classes", "class_id"),
+    ("users", "user_classes", "user_id"),
+    ("lesson_types", "lessons", "type_id"),
+    ("lessons", "lesson_classes", "lesson_id"),
+    ("lessons", "lesson_rooms", "lesson_id"),
+    ("lessons", "lesson_teachers", "lesson_id"),
+    ("rooms", "lesson_rooms", "room_id"),
+    ("teachers", "lesson_teachers", "teacher_id")
+];
+
+/// Soft delete the rows depending on the rows of `table` deleted at `deleted_at`
+fn cascade_delete(tx: &rusqlite::Transaction, table: &str, deleted_at: &Value) -> rusqlite::Result<()> {
+    for (_, dependent, column) in CASCADES.iter().filter(|(t, _, _)| *t == table) {
+        tx.execute(&format!("
+            UPDATE {dependent}
+            SET deleted_at = ?1
+            WHERE deleted_at IS NULL AND {column} IN (SELECT id FROM {table} WHERE deleted_at = ?1)
+        "), [deleted_at])?;
+        cascade_delete(tx, dependent, deleted_at)?;
+    }
+    Ok(())
+}
+
+/// Restore the rows of `table` matching `condition`, after the rows deleted with them
+fn cascade_restore(tx: &rusqlite::Transaction, table: &str, condition: &str, params: &[Value]) -> rusqlite::Result<usize> {
+    for (_, dependent, column) in CASCADES.iter().filter(|(t, _, _)| *t == table) {
+        // Rows deleted at the same time as a row that is restored
+        let dependent_condition = format!(
+            "{column} IN (SELECT id FROM {table} WHERE ({condition}) AND deleted_at = {dependent}.deleted_at)"
+        );
+        cascade_restore(tx, dependent, &dependent_condition, params)?;
     }
+    tx.execute(&format!("
+        UPDATE {table}
+        SET deleted_at = NULL
+        WHERE ({condition}) AND deleted_at IS NOT NULL
+    "), rusqlite::params_from_iter(params))
 }
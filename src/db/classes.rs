@@ -0,0 +1,128 @@
+use rusqlite::Row;
+use anyhow::Result;
+use super::{Conditions, DB, id, text};
+
+const COLUMNS: &str = "id, name, join_code";
+
+#[derive(Clone, Debug)]
+pub struct Class {
+    pub id: usize,
+    pub name: String,
+    /// Code the students enter to join the class
+    pub join_code: String
+}
+impl Class {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            join_code: row.get(2)?
+        })
+    }
+}
+
+/// Random code of 8 characters, easy to type
+fn new_join_code() -> String {
+    hex::encode_upper(rand::random::<[u8; 4]>())
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ClassFilter {
+    pub include_deleted: bool
+}
+
+impl DB {
+    pub async fn insert_class(&self, name: String) -> Result<usize> {
+        let id = self.conn.call(|conn| {
+            conn.execute("
+                INSERT INTO classes (name, join_code, created_at)
+                VALUES (?1, ?2, datetime('now'))
+            ", [name, new_join_code()])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_class(&self, class_id: usize) -> Result<Option<Class>> {
+        self.select_one(format!("SELECT {} FROM classes WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(class_id)], Class::from_row).await
+    }
+    /// The class with this join code, ignoring the case and the spaces around it
+    pub async fn get_class_by_join_code(&self, join_code: &str) -> Result<Option<Class>> {
+        self.select_one(
+            format!("SELECT {} FROM classes WHERE join_code = ? AND deleted_at IS NULL", COLUMNS),
+            vec![text(join_code.trim().to_uppercase())], Class::from_row
+        ).await
+    }
+    pub async fn list_classes(&self, filter: ClassFilter) -> Result<Vec<Class>> {
+        let mut conditions = Conditions::default();
+        conditions.not_deleted(filter.include_deleted);
+        self.select(format!("SELECT {} FROM classes {} ORDER BY name", COLUMNS, conditions.sql()), conditions.params, Class::from_row).await
+    }
+    /// Returns false if the class doesn't exist
+    #[allow(dead_code)] // No route renames or deletes classes yet
+    pub async fn rename_class(&self, class_id: usize, name: String) -> Result<bool> {
+        let changed = self.execute("UPDATE classes SET name = ? WHERE id = ?".to_string(), vec![text(name), id(class_id)]).await?;
+        Ok(changed > 0)
+    }
+    /// Replace the join code of the class, so the old one can't be used anymore. Returns the new code, or `None` if the class doesn't exist
+    pub async fn rotate_join_code(&self, class_id: usize) -> Result<Option<String>> {
+        let join_code = new_join_code();
+        let changed = self.execute("UPDATE classes SET join_code = ? WHERE id = ? AND deleted_at IS NULL".to_string(), vec![text(join_code.clone()), id(class_id)]).await?;
+        Ok(if changed > 0 { Some(join_code) } else { None })
+    }
+    #[allow(dead_code)]
+    pub async fn delete_class(&self, class_id: usize) -> Result<bool> {
+        self.soft_delete("classes", "id = ?", vec![id(class_id)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::TrashKind;
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let a = db.insert_class("1A".to_string()).await.unwrap();
+        let b = db.insert_class("1B".to_string()).await.unwrap();
+        assert!(db.insert_class("1A".to_string()).await.is_err());
+
+        assert_eq!(db.get_class(a).await.unwrap().unwrap().name, "1A");
+        assert!(db.get_class(b + 1).await.unwrap().is_none());
+
+        assert!(db.rename_class(b, "2B".to_string()).await.unwrap());
+        assert!(!db.rename_class(b + 1, "3B".to_string()).await.unwrap());
+        assert_eq!(db.get_class(b).await.unwrap().unwrap().name, "2B");
+
+        assert!(db.delete_class(a).await.unwrap());
+        assert!(!db.delete_class(a).await.unwrap());
+        assert!(db.get_class(a).await.unwrap().is_none());
+        let names = |classes: Vec<Class>| classes.into_iter().map(|c| c.name).collect::<Vec<_>>();
+        assert_eq!(names(db.list_classes(ClassFilter::default()).await.unwrap()), ["2B"]);
+        assert_eq!(names(db.list_classes(ClassFilter { include_deleted: true }).await.unwrap()), ["1A", "2B"]);
+
+        assert!(db.restore_item(TrashKind::Class, a).await.unwrap());
+        assert!(!db.restore_item(TrashKind::Class, a).await.unwrap());
+        assert_eq!(db.list_classes(ClassFilter::default()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn join_codes() {
+        let db = DB::new(None).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let code = db.get_class(class).await.unwrap().unwrap().join_code;
+        assert_eq!(code.len(), 8);
+        let joined = db.get_class_by_join_code(&format!(" {} ", code.to_lowercase())).await.unwrap();
+        assert_eq!(joined.map(|c| c.id), Some(class));
+
+        // The old code stops working
+        let new_code = db.rotate_join_code(class).await.unwrap().unwrap();
+        assert_ne!(new_code, code);
+        assert!(db.get_class_by_join_code(&code).await.unwrap().is_none());
+        assert_eq!(db.get_class_by_join_code(&new_code).await.unwrap().map(|c| c.id), Some(class));
+        assert!(db.rotate_join_code(class + 1).await.unwrap().is_none());
+
+        db.delete_class(class).await.unwrap();
+        assert!(db.get_class_by_join_code(&new_code).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use rusqlite::Row;
+use chrono::NaiveDate;
+use anyhow::Result;
+use super::{Conditions, DB, date, id};
+
+const COLUMNS: &str = "id, name, start, end";
+
+/// Days without lessons (holidays, closure of the school...)
+#[derive(Clone, Debug)]
+pub struct Holiday {
+    pub id: usize,
+    pub name: String,
+    pub start: NaiveDate,
+    /// Last day of the holidays, included
+    pub end: NaiveDate
+}
+impl Holiday {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            start: row.get(2)?,
+            end: row.get(3)?
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HolidayFilter {
+    /// Holidays ending on this day or after
+    pub from: Option<NaiveDate>,
+    /// Holidays starting on this day or before
+    pub to: Option<NaiveDate>,
+    pub include_deleted: bool
+}
+
+impl DB {
+    pub async fn insert_holiday(&self, name: String, start: NaiveDate, end: NaiveDate) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO holidays (name, start, end, created_at)
+                VALUES (?1, ?2, ?3, datetime('now'))
+            ", rusqlite::params![name, start, end])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_holiday(&self, holiday_id: usize) -> Result<Option<Holiday>> {
+        self.select_one(format!("SELECT {} FROM holidays WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(holiday_id)], Holiday::from_row).await
+    }
+    pub async fn list_holidays(&self, filter: HolidayFilter) -> Result<Vec<Holiday>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("end >= ?", filter.from, date);
+        conditions.add_opt("start <= ?", filter.to, date);
+        conditions.not_deleted(filter.include_deleted);
+        self.select(
+            format!("SELECT {} FROM holidays {} ORDER BY start", COLUMNS, conditions.sql()),
+            conditions.params, Holiday::from_row
+        ).await
+    }
+    /// Returns false if the holidays don't exist
+    pub async fn update_holiday(&self, holiday_id: usize, name: String, start: NaiveDate, end: NaiveDate) -> Result<bool> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE holidays
+                SET name = ?2, start = ?3, end = ?4
+                WHERE id = ?1
+            ", rusqlite::params![holiday_id, name, start, end])
+        }).await?;
+        Ok(changed > 0)
+    }
+    pub async fn delete_holiday(&self, holiday_id: usize) -> Result<bool> {
+        self.soft_delete("holidays", "id = ?", vec![id(holiday_id)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::TrashKind;
+    use super::*;
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let autumn = db.insert_holiday("Toussaint".to_string(), day(10, 21), day(11, 5)).await.unwrap();
+        let christmas = db.insert_holiday("Noël".to_string(), day(12, 23), day(12, 31)).await.unwrap();
+        let holiday = db.get_holiday(autumn).await.unwrap().unwrap();
+        assert!(holiday.contains(day(11, 5)) && !holiday.contains(day(11, 6)));
+
+        let ids = |holidays: Vec<Holiday>| holidays.into_iter().map(|h| h.id).collect::<Vec<_>>();
+        let november = HolidayFilter { from: Some(day(11, 1)), to: Some(day(11, 30)), ..Default::default() };
+        assert_eq!(ids(db.list_holidays(november.clone()).await.unwrap()), [autumn]);
+        assert_eq!(ids(db.list_holidays(HolidayFilter::default()).await.unwrap()), [autumn, christmas]);
+
+        assert!(db.update_holiday(autumn, "Toussaint".to_string(), day(10, 21), day(10, 31)).await.unwrap());
+        assert!(db.list_holidays(november).await.unwrap().is_empty());
+
+        assert!(db.delete_holiday(christmas).await.unwrap());
+        assert!(db.get_holiday(christmas).await.unwrap().is_none());
+        assert_eq!(ids(db.list_holidays(HolidayFilter::default()).await.unwrap()), [autumn]);
+        assert!(db.restore_item(TrashKind::Holiday, christmas).await.unwrap());
+        assert_eq!(db.list_holidays(HolidayFilter::default()).await.unwrap().len(), 2);
+    }
+}
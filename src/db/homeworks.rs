@@ -0,0 +1,158 @@
+use rusqlite::Row;
+use chrono::NaiveDate;
+use anyhow::Result;
+use super::{Conditions, DB, date, id};
+
+const COLUMNS: &str = "id, name, description, class_id, lesson_id, due_date";
+
+#[derive(Clone, Debug)]
+pub struct Homework {
+    pub id: usize,
+    pub name: String,
+    pub description: String,
+    pub class_id: usize,
+    pub lesson_id: Option<usize>,
+    pub due_date: NaiveDate
+}
+impl Homework {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            class_id: row.get(3)?,
+            lesson_id: row.get(4)?,
+            due_date: row.get(5)?
+        })
+    }
+}
+
+/// Fields of a homework that can be set when creating or editing it
+#[derive(Clone, Debug)]
+pub struct HomeworkData {
+    pub name: String,
+    pub description: String,
+    pub class_id: usize,
+    pub lesson_id: Option<usize>,
+    pub due_date: NaiveDate
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HomeworkFilter {
+    pub class_id: Option<usize>,
+    /// Homeworks of any of these classes
+    pub class_ids: Option<Vec<usize>>,
+    pub lesson_id: Option<usize>,
+    /// Homeworks for any of these lessons
+    pub lesson_ids: Option<Vec<usize>>,
+    pub created_by: Option<usize>,
+    /// Homeworks due on this day or after
+    pub due_from: Option<NaiveDate>,
+    pub include_deleted: bool
+}
+
+impl DB {
+    pub async fn insert_homework(&self, data: HomeworkData, created_by: usize) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO homeworks (name, description, class_id, created_by, lesson_id, due_date, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+            ", rusqlite::params![data.name, data.description, data.class_id, created_by, data.lesson_id, data.due_date])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_homework(&self, homework_id: usize) -> Result<Option<Homework>> {
+        self.select_one(format!("SELECT {} FROM homeworks WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(homework_id)], Homework::from_row).await
+    }
+    pub async fn list_homeworks(&self, filter: HomeworkFilter) -> Result<Vec<Homework>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("class_id = ?", filter.class_id, id);
+        conditions.add_opt("lesson_id = ?", filter.lesson_id, id);
+        if let Some(class_ids) = filter.class_ids {
+            conditions.add_in("class_id IN ({})", class_ids.into_iter().map(id).collect());
+        }
+        if let Some(lesson_ids) = filter.lesson_ids {
+            conditions.add_in("lesson_id IN ({})", lesson_ids.into_iter().map(id).collect());
+        }
+        conditions.add_opt("created_by = ?", filter.created_by, id);
+        conditions.add_opt("due_date >= ?", filter.due_from, date);
+        conditions.not_deleted(filter.include_deleted);
+        self.select(
+            format!("SELECT {} FROM homeworks {} ORDER BY due_date, class_id, id", COLUMNS, conditions.sql()),
+            conditions.params, Homework::from_row
+        ).await
+    }
+    /// Returns false if the homework doesn't exist
+    #[allow(dead_code)] // Homeworks can't be edited from the site yet, they are deleted and created again
+    pub async fn update_homework(&self, homework_id: usize, data: HomeworkData) -> Result<bool> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE homeworks
+                SET name = ?2, description = ?3, class_id = ?4, lesson_id = ?5, due_date = ?6
+                WHERE id = ?1
+            ", rusqlite::params![homework_id, data.name, data.description, data.class_id, data.lesson_id, data.due_date])
+        }).await?;
+        Ok(changed > 0)
+    }
+    pub async fn delete_homework(&self, homework_id: usize) -> Result<bool> {
+        self.soft_delete("homeworks", "id = ?", vec![id(homework_id)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::TrashKind;
+    use super::*;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 9, day).unwrap()
+    }
+    fn data(name: &str, class_id: usize, due: u32) -> HomeworkData {
+        HomeworkData {
+            name: name.to_string(),
+            description: String::new(),
+            class_id,
+            lesson_id: None,
+            due_date: day(due)
+        }
+    }
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+
+        let maths = db.insert_homework(data("Maths", class, 5), user).await.unwrap();
+        let english = db.insert_homework(data("English", other, 4), user).await.unwrap();
+        let homework = db.get_homework(maths).await.unwrap().unwrap();
+        assert_eq!((homework.name.as_str(), homework.class_id, homework.due_date), ("Maths", class, day(5)));
+        assert!(db.get_homework(english + 1).await.unwrap().is_none());
+
+        let in_class = db.list_homeworks(HomeworkFilter { class_id: Some(class), ..Default::default() }).await.unwrap();
+        assert_eq!(in_class.iter().map(|h| h.id).collect::<Vec<_>>(), [maths]);
+        assert_eq!(db.list_homeworks(HomeworkFilter { created_by: Some(user), ..Default::default() }).await.unwrap().len(), 2);
+        // Sorted by due date
+        let ids = |homeworks: Vec<Homework>| homeworks.into_iter().map(|h| h.id).collect::<Vec<_>>();
+        let in_classes = HomeworkFilter { class_ids: Some(vec![class, other]), ..Default::default() };
+        assert_eq!(ids(db.list_homeworks(in_classes).await.unwrap()), [english, maths]);
+        assert!(db.list_homeworks(HomeworkFilter { class_ids: Some(Vec::new()), ..Default::default() }).await.unwrap().is_empty());
+        assert_eq!(ids(db.list_homeworks(HomeworkFilter { due_from: Some(day(5)), ..Default::default() }).await.unwrap()), [maths]);
+
+        let mut new = data("Maths p.42", other, 6);
+        new.description = "Exercise 3".to_string();
+        assert!(db.update_homework(maths, new).await.unwrap());
+        assert!(!db.update_homework(english + 1, data("", class, 6)).await.unwrap());
+        let homework = db.get_homework(maths).await.unwrap().unwrap();
+        assert_eq!((homework.name.as_str(), homework.description.as_str(), homework.class_id), ("Maths p.42", "Exercise 3", other));
+
+        assert!(db.delete_homework(english).await.unwrap());
+        assert!(db.get_homework(english).await.unwrap().is_none());
+        assert_eq!(db.list_homeworks(HomeworkFilter::default()).await.unwrap().len(), 1);
+        assert_eq!(db.list_homeworks(HomeworkFilter { include_deleted: true, ..Default::default() }).await.unwrap().len(), 2);
+        assert!(db.restore_item(TrashKind::Homework, english).await.unwrap());
+        assert_eq!(db.list_homeworks(HomeworkFilter::default()).await.unwrap().len(), 2);
+    }
+}
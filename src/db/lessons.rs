@@ -0,0 +1,391 @@
+use rusqlite::Row;
+use chrono::{NaiveDate, NaiveDateTime};
+use anyhow::Result;
+use super::{Conditions, DB, date, datetime, id, text};
+
+const TYPE_COLUMNS: &str = "id, name";
+const COLUMNS: &str = "id, type_id, start, end, repeat_weeks, repeat_until";
+const CLASS_COLUMNS: &str = "lesson_id, class_id";
+
+/// Subject of a lesson (ex: "Maths")
+#[derive(Clone, Debug)]
+pub struct LessonType {
+    pub id: usize,
+    pub name: String
+}
+impl LessonType {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?
+        })
+    }
+}
+
+/// Recurrence of a lesson, which takes place every `weeks` weeks on the day of its first occurrence
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Repeat {
+    /// 1 for every week, 2 for A/B weeks
+    pub weeks: u32,
+    /// Day of the last occurrence
+    pub until: NaiveDate
+}
+
+/// A lesson, or a series of lessons if it repeats. `start` and `end` are the ones of the first occurrence
+#[derive(Clone, Debug)]
+pub struct Lesson {
+    pub id: usize,
+    pub type_id: usize,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub repeat: Option<Repeat>
+}
+impl Lesson {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            type_id: row.get(1)?,
+            start: row.get(2)?,
+            end: row.get(3)?,
+            repeat: match row.get::<_, Option<u32>>(4)? {
+                None => None,
+                Some(weeks) => Some(Repeat { weeks, until: row.get(5)? })
+            }
+        })
+    }
+}
+
+/// Fields of a lesson that can be set when creating or editing it
+#[derive(Clone, Debug)]
+pub struct LessonData {
+    pub type_id: usize,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub repeat: Option<Repeat>
+}
+
+/// Class attending a lesson
+#[derive(Clone, Debug)]
+pub struct LessonClass {
+    pub lesson_id: usize,
+    pub class_id: usize
+}
+impl LessonClass {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            lesson_id: row.get(0)?,
+            class_id: row.get(1)?
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LessonFilter {
+    pub type_id: Option<usize>,
+    /// Lessons attended by this class
+    pub class_id: Option<usize>,
+    /// Lessons attended by any of these classes
+    pub class_ids: Option<Vec<usize>>,
+    pub teacher_id: Option<usize>,
+    /// Lessons in this room, or with an occurrence moved to it
+    pub room_id: Option<usize>,
+    /// Only the lessons with homeworks to do for them
+    pub with_homework: bool,
+    /// Lessons ending after this date, or repeating until this day or after
+    pub from: Option<NaiveDateTime>,
+    /// Lessons starting before this date (their first occurrence if they repeat)
+    pub to: Option<NaiveDateTime>,
+    pub include_deleted: bool
+}
+
+impl DB {
+    // lesson_types
+    // The subjects are only managed in the database for now
+    #[allow(dead_code)]
+    pub async fn insert_lesson_type(&self, name: String) -> Result<usize> {
+        let id = self.conn.call(|conn| {
+            conn.execute("
+                INSERT INTO lesson_types (name, created_at)
+                VALUES (?1, datetime('now'))
+            ", [name])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_lesson_type(&self, type_id: usize) -> Result<Option<LessonType>> {
+        self.select_one(format!("SELECT {} FROM lesson_types WHERE id = ? AND deleted_at IS NULL", TYPE_COLUMNS), vec![id(type_id)], LessonType::from_row).await
+    }
+    pub async fn list_lesson_types(&self, include_deleted: bool) -> Result<Vec<LessonType>> {
+        let mut conditions = Conditions::default();
+        conditions.not_deleted(include_deleted);
+        self.select(
+            format!("SELECT {} FROM lesson_types {} ORDER BY name", TYPE_COLUMNS, conditions.sql()),
+            conditions.params, LessonType::from_row
+        ).await
+    }
+    /// Returns false if the lesson type doesn't exist
+    #[allow(dead_code)]
+    pub async fn rename_lesson_type(&self, type_id: usize, name: String) -> Result<bool> {
+        let changed = self.execute("UPDATE lesson_types SET name = ? WHERE id = ?".to_string(), vec![text(name), id(type_id)]).await?;
+        Ok(changed > 0)
+    }
+    #[allow(dead_code)]
+    pub async fn delete_lesson_type(&self, type_id: usize) -> Result<bool> {
+        self.soft_delete("lesson_types", "id = ?", vec![id(type_id)]).await
+    }
+
+    // lessons
+    pub async fn insert_lesson(&self, data: LessonData) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO lessons (type_id, start, end, repeat_weeks, repeat_until, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+            ", rusqlite::params![data.type_id, data.start, data.end, data.repeat.map(|r| r.weeks), data.repeat.map(|r| r.until)])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_lesson(&self, lesson_id: usize) -> Result<Option<Lesson>> {
+        self.select_one(format!("SELECT {} FROM lessons WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(lesson_id)], Lesson::from_row).await
+    }
+    pub async fn list_lessons(&self, filter: LessonFilter) -> Result<Vec<Lesson>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("type_id = ?", filter.type_id, id);
+        conditions.add_opt(
+            "id IN (SELECT lesson_id FROM lesson_classes WHERE class_id = ? AND deleted_at IS NULL)",
+            filter.class_id, id
+        );
+        if let Some(class_ids) = filter.class_ids {
+            conditions.add_in(
+                "id IN (SELECT lesson_id FROM lesson_classes WHERE class_id IN ({}) AND deleted_at IS NULL)",
+                class_ids.into_iter().map(id).collect()
+            );
+        }
+        conditions.add_opt(
+            "id IN (SELECT lesson_id FROM lesson_teachers WHERE teacher_id = ? AND deleted_at IS NULL)",
+            filter.teacher_id, id
+        );
+        if let Some(room_id) = filter.room_id {
+            conditions.add_many("(
+                id IN (SELECT lesson_id FROM lesson_rooms WHERE room_id = ? AND deleted_at IS NULL)
+                OR id IN (SELECT lesson_id FROM lesson_exceptions WHERE room_id = ?)
+            )", vec![id(room_id), id(room_id)]);
+        }
+        if filter.with_homework {
+            conditions.add_clause("id IN (SELECT lesson_id FROM homeworks WHERE lesson_id IS NOT NULL AND deleted_at IS NULL)");
+        }
+        if filter.from.is_some() || filter.to.is_some() {
+            // The lessons spanning the range, or with an occurrence moved into it
+            let mut window = Conditions::default();
+            let mut moved = Conditions::default();
+            moved.add_clause("start IS NOT NULL");
+            if let Some(from) = filter.from {
+                window.add_many("(end > ? OR repeat_until >= ?)", vec![datetime(from), date(from.date())]);
+                moved.add("end > ?", datetime(from));
+            }
+            if let Some(to) = filter.to {
+                window.add("start < ?", datetime(to));
+                moved.add("start < ?", datetime(to));
+            }
+            conditions.add_many(
+                &format!(
+                    "(({}) OR id IN (SELECT lesson_id FROM lesson_exceptions WHERE {}))",
+                    window.clauses.join(" AND "), moved.clauses.join(" AND ")
+                ),
+                window.params.into_iter().chain(moved.params).collect()
+            );
+        }
+        conditions.not_deleted(filter.include_deleted);
+        self.select(format!("SELECT {} FROM lessons {} ORDER BY start", COLUMNS, conditions.sql()), conditions.params, Lesson::from_row).await
+    }
+    /// Returns false if the lesson doesn't exist
+    pub async fn update_lesson(&self, lesson_id: usize, data: LessonData) -> Result<bool> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE lessons
+                SET type_id = ?2, start = ?3, end = ?4, repeat_weeks = ?5, repeat_until = ?6
+                WHERE id = ?1
+            ", rusqlite::params![lesson_id, data.type_id, data.start, data.end, data.repeat.map(|r| r.weeks), data.repeat.map(|r| r.until)])
+        }).await?;
+        Ok(changed > 0)
+    }
+    #[allow(dead_code)] // The site only cancels single occurrences of a lesson
+    pub async fn delete_lesson(&self, lesson_id: usize) -> Result<bool> {
+        self.soft_delete("lessons", "id = ?", vec![id(lesson_id)]).await
+    }
+
+    // lesson_classes
+    pub async fn list_lesson_classes(&self, lesson_id: usize, include_deleted: bool) -> Result<Vec<LessonClass>> {
+        let mut conditions = Conditions::default();
+        conditions.add("lesson_id = ?", id(lesson_id));
+        conditions.not_deleted(include_deleted);
+        self.select(
+            format!("SELECT {} FROM lesson_classes {} ORDER BY class_id", CLASS_COLUMNS, conditions.sql()),
+            conditions.params, LessonClass::from_row
+        ).await
+    }
+    /// Classes attending any of the lessons
+    pub async fn list_classes_of_lessons(&self, lesson_ids: Vec<usize>) -> Result<Vec<LessonClass>> {
+        let mut conditions = Conditions::default();
+        conditions.add_in("lesson_id IN ({})", lesson_ids.into_iter().map(id).collect());
+        conditions.not_deleted(false);
+        self.select(
+            format!("SELECT {} FROM lesson_classes {} ORDER BY lesson_id, class_id", CLASS_COLUMNS, conditions.sql()),
+            conditions.params, LessonClass::from_row
+        ).await
+    }
+    pub async fn set_lesson_classes(&self, lesson_id: usize, class_ids: Vec<usize>) -> Result<()> {
+        self.set_lesson_links("lesson_classes", "class_id", lesson_id, class_ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::db::{LessonException, TrashKind};
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 9, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+    fn data(type_id: usize, day: u32, hour: u32) -> LessonData {
+        LessonData { type_id, start: at(day, hour), end: at(day, hour + 1), repeat: None }
+    }
+    fn ids(lessons: Vec<Lesson>) -> Vec<usize> {
+        lessons.into_iter().map(|l| l.id).collect()
+    }
+
+    #[tokio::test]
+    async fn lesson_types() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        db.insert_lesson_type("English".to_string()).await.unwrap();
+        assert!(db.insert_lesson_type("Maths".to_string()).await.is_err());
+
+        assert!(db.rename_lesson_type(maths, "Mathematics".to_string()).await.unwrap());
+        assert_eq!(db.get_lesson_type(maths).await.unwrap().unwrap().name, "Mathematics");
+
+        assert!(db.delete_lesson_type(maths).await.unwrap());
+        assert!(db.get_lesson_type(maths).await.unwrap().is_none());
+        assert_eq!(db.list_lesson_types(false).await.unwrap().len(), 1);
+        assert_eq!(db.list_lesson_types(true).await.unwrap().len(), 2);
+        assert!(db.restore_item(TrashKind::LessonType, maths).await.unwrap());
+        assert_eq!(db.list_lesson_types(false).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn lessons() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let english = db.insert_lesson_type("English".to_string()).await.unwrap();
+
+        let monday = db.insert_lesson(data(maths, 4, 8)).await.unwrap();
+        let lesson = db.get_lesson(monday).await.unwrap().unwrap();
+        assert_eq!((lesson.type_id, lesson.start, lesson.end, lesson.repeat), (maths, at(4, 8), at(4, 9), None));
+
+        let weekly = Repeat { weeks: 1, until: at(26, 8).date() };
+        assert!(db.update_lesson(monday, LessonData { repeat: Some(weekly), ..data(english, 5, 10) }).await.unwrap());
+        assert!(!db.update_lesson(monday + 1, data(maths, 4, 8)).await.unwrap());
+        let lesson = db.get_lesson(monday).await.unwrap().unwrap();
+        assert_eq!((lesson.type_id, lesson.start, lesson.repeat), (english, at(5, 10), Some(weekly)));
+
+        assert!(db.delete_lesson(monday).await.unwrap());
+        assert!(db.get_lesson(monday).await.unwrap().is_none());
+        assert!(db.list_lessons(LessonFilter::default()).await.unwrap().is_empty());
+        assert_eq!(db.list_lessons(LessonFilter { include_deleted: true, ..Default::default() }).await.unwrap().len(), 1);
+        assert!(db.restore_item(TrashKind::Lesson, monday).await.unwrap());
+        assert_eq!(ids(db.list_lessons(LessonFilter::default()).await.unwrap()), [monday]);
+    }
+
+    #[tokio::test]
+    async fn filters() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let english = db.insert_lesson_type("English".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+        let monday = db.insert_lesson(data(maths, 4, 8)).await.unwrap();
+        let tuesday = db.insert_lesson(data(english, 5, 8)).await.unwrap();
+        assert_eq!(ids(db.list_lessons(LessonFilter::default()).await.unwrap()), [monday, tuesday]);
+        assert_eq!(ids(db.list_lessons(LessonFilter { type_id: Some(english), ..Default::default() }).await.unwrap()), [tuesday]);
+
+        // Classes
+        db.set_lesson_classes(monday, vec![class]).await.unwrap();
+        db.set_lesson_classes(tuesday, vec![other]).await.unwrap();
+        assert_eq!(ids(db.list_lessons(LessonFilter { class_id: Some(class), ..Default::default() }).await.unwrap()), [monday]);
+        let in_classes = LessonFilter { class_ids: Some(vec![class, other]), ..Default::default() };
+        assert_eq!(ids(db.list_lessons(in_classes).await.unwrap()), [monday, tuesday]);
+        let classes = db.list_classes_of_lessons(vec![monday, tuesday]).await.unwrap();
+        assert_eq!(classes.iter().map(|c| (c.lesson_id, c.class_id)).collect::<Vec<_>>(), [(monday, class), (tuesday, other)]);
+
+        // The removed classes are kept, but don't match anymore
+        db.set_lesson_classes(tuesday, vec![class]).await.unwrap();
+        assert_eq!(db.list_lesson_classes(tuesday, false).await.unwrap().iter().map(|c| c.class_id).collect::<Vec<_>>(), [class]);
+        assert_eq!(db.list_lesson_classes(tuesday, true).await.unwrap().len(), 2);
+        assert!(db.list_lessons(LessonFilter { class_id: Some(other), ..Default::default() }).await.unwrap().is_empty());
+        db.set_lesson_classes(tuesday, vec![other]).await.unwrap();
+        assert_eq!(db.list_lesson_classes(tuesday, true).await.unwrap().len(), 2);
+        assert_eq!(ids(db.list_lessons(LessonFilter { class_id: Some(other), ..Default::default() }).await.unwrap()), [tuesday]);
+
+        // Rooms and teachers
+        let room = db.insert_room("B12".to_string()).await.unwrap();
+        db.set_lesson_rooms(tuesday, vec![room]).await.unwrap();
+        assert_eq!(ids(db.list_lessons(LessonFilter { room_id: Some(room), ..Default::default() }).await.unwrap()), [tuesday]);
+        let teacher = db.insert_teacher("M. Martin".to_string(), None).await.unwrap();
+        db.set_lesson_teachers(monday, vec![teacher]).await.unwrap();
+        assert_eq!(ids(db.list_lessons(LessonFilter { teacher_id: Some(teacher), ..Default::default() }).await.unwrap()), [monday]);
+
+        // Homeworks
+        let user = db.insert_user("bob".to_string(), "hash".to_string()).await.unwrap();
+        db.insert_homework(crate::db::HomeworkData {
+            name: "Exercises".to_string(),
+            description: String::new(),
+            class_id: class,
+            lesson_id: Some(monday),
+            due_date: at(4, 8).date()
+        }, user).await.unwrap();
+        assert_eq!(ids(db.list_lessons(LessonFilter { with_homework: true, ..Default::default() }).await.unwrap()), [monday]);
+    }
+
+    #[tokio::test]
+    async fn recurrence_window() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let monday = db.insert_lesson(data(maths, 4, 8)).await.unwrap();
+        let weekly = Repeat { weeks: 1, until: at(26, 8).date() };
+        let tuesday = db.insert_lesson(LessonData { repeat: Some(weekly), ..data(maths, 5, 10) }).await.unwrap();
+
+        let window = |from, to| LessonFilter { from, to, ..Default::default() };
+        assert_eq!(ids(db.list_lessons(window(Some(at(4, 9)), None)).await.unwrap()), [tuesday]);
+        assert_eq!(ids(db.list_lessons(window(None, Some(at(5, 10)))).await.unwrap()), [monday]);
+        assert_eq!(ids(db.list_lessons(window(Some(at(4, 8)), Some(at(5, 11)))).await.unwrap()), [monday, tuesday]);
+        // Lessons repeating in the range, until the day of their last occurrence
+        assert_eq!(ids(db.list_lessons(window(Some(at(20, 8)), Some(at(21, 8)))).await.unwrap()), [tuesday]);
+        assert_eq!(ids(db.list_lessons(window(Some(at(26, 12)), None)).await.unwrap()), [tuesday]);
+        assert!(db.list_lessons(window(Some(at(27, 8)), None)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn moved_exceptions() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let room = db.insert_room("B12".to_string()).await.unwrap();
+        let lesson = db.insert_lesson(data(maths, 6, 8)).await.unwrap();
+        db.set_lesson_exception(LessonException {
+            lesson_id: lesson,
+            date: at(6, 8).date(),
+            cancelled: false,
+            moved_to: Some((at(13, 8), at(13, 9))),
+            room_id: Some(room)
+        }).await.unwrap();
+
+        // Found both in its planned window and in the one it was moved to
+        let window = |from, to| LessonFilter { from: Some(from), to: Some(to), ..Default::default() };
+        assert_eq!(ids(db.list_lessons(window(at(6, 0), at(7, 0))).await.unwrap()), [lesson]);
+        assert_eq!(ids(db.list_lessons(window(at(13, 0), at(14, 0))).await.unwrap()), [lesson]);
+        assert!(db.list_lessons(window(at(13, 9), at(14, 0))).await.unwrap().is_empty());
+        assert!(db.list_lessons(window(at(20, 0), at(21, 0))).await.unwrap().is_empty());
+        // The room of the exception is enough to match
+        assert_eq!(ids(db.list_lessons(LessonFilter { room_id: Some(room), ..Default::default() }).await.unwrap()), [lesson]);
+    }
+}
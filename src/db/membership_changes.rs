@@ -0,0 +1,117 @@
+use rusqlite::{Row, ToSql, types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef}};
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use super::{DB, Role, id};
+
+const COLUMNS: &str = "actor_id, user_id, action, role, created_at";
+
+/// What was changed in the members of a class
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MembershipAction {
+    /// A user joined the class with its join code
+    Join,
+    Remove,
+    ChangeRole,
+    /// The join code was replaced by a new one
+    RotateCode
+}
+impl MembershipAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Join => "join",
+            Self::Remove => "remove",
+            Self::ChangeRole => "change_role",
+            Self::RotateCode => "rotate_code"
+        }
+    }
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "join" => Some(Self::Join),
+            "remove" => Some(Self::Remove),
+            "change_role" => Some(Self::ChangeRole),
+            "rotate_code" => Some(Self::RotateCode),
+            _ => None
+        }
+    }
+}
+impl ToSql for MembershipAction {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+impl FromSql for MembershipAction {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Self::parse(value.as_str()?).ok_or(FromSqlError::InvalidType)
+    }
+}
+
+/// Entry of the audit log of a class
+#[derive(Clone, Debug)]
+pub struct MembershipChange {
+    /// `None` if the user who made the change was removed from the database
+    pub actor_id: Option<usize>,
+    /// Member concerned by the change, `None` for `RotateCode`
+    pub user_id: Option<usize>,
+    pub action: MembershipAction,
+    /// Role of the member after a `Join` or `ChangeRole`
+    pub role: Option<Role>,
+    pub created_at: NaiveDateTime
+}
+impl MembershipChange {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            actor_id: row.get(0)?,
+            user_id: row.get(1)?,
+            action: row.get(2)?,
+            role: row.get(3)?,
+            created_at: row.get(4)?
+        })
+    }
+}
+
+impl DB {
+    pub async fn insert_membership_change(&self, class_id: usize, actor_id: usize, user_id: Option<usize>, action: MembershipAction, role: Option<Role>) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO membership_changes (class_id, actor_id, user_id, action, role, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+            ", rusqlite::params![class_id, actor_id, user_id, action, role])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    /// Changes to the members of the class, newest first
+    pub async fn list_membership_changes(&self, class_id: usize, limit: usize) -> Result<Vec<MembershipChange>> {
+        self.select(
+            format!("SELECT {} FROM membership_changes WHERE class_id = ? ORDER BY created_at DESC, id DESC LIMIT ?", COLUMNS),
+            vec![id(class_id), id(limit)], MembershipChange::from_row
+        ).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn audit() {
+        let db = DB::new(None).await.unwrap();
+        let teacher = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let student = db.insert_user("bob".to_string(), "hash".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+
+        db.insert_membership_change(class, student, Some(student), MembershipAction::Join, Some(Role::Student)).await.unwrap();
+        db.insert_membership_change(class, teacher, Some(student), MembershipAction::ChangeRole, Some(Role::Delegate)).await.unwrap();
+        db.insert_membership_change(class, teacher, None, MembershipAction::RotateCode, None).await.unwrap();
+        db.insert_membership_change(other, teacher, Some(student), MembershipAction::Remove, None).await.unwrap();
+
+        let changes = db.list_membership_changes(class, 10).await.unwrap();
+        assert_eq!(changes.iter().map(|c| (c.actor_id, c.user_id, c.action, c.role)).collect::<Vec<_>>(), [
+            (Some(teacher), None, MembershipAction::RotateCode, None),
+            (Some(teacher), Some(student), MembershipAction::ChangeRole, Some(Role::Delegate)),
+            (Some(student), Some(student), MembershipAction::Join, Some(Role::Student))
+        ]);
+        assert_eq!(db.list_membership_changes(class, 1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,116 @@
+use rusqlite::Row;
+use anyhow::Result;
+use super::{Conditions, DB, Role, id, role};
+
+const COLUMNS: &str = "user_id, class_id, role";
+
+/// Membership of a user in a class
+#[derive(Clone, Debug)]
+pub struct Membership {
+    pub user_id: usize,
+    pub class_id: usize,
+    pub role: Role
+}
+impl Membership {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            user_id: row.get(0)?,
+            class_id: row.get(1)?,
+            role: row.get(2)?
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MembershipFilter {
+    pub user_id: Option<usize>,
+    pub class_id: Option<usize>,
+    pub role: Option<Role>,
+    pub include_deleted: bool
+}
+
+impl DB {
+    /// Add a user to a class, or restore their membership and change their role if they already were in it
+    pub async fn insert_membership(&self, user_id: usize, class_id: usize, role: Role) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO user_classes (user_id, class_id, role)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT (user_id, class_id) DO UPDATE SET role = excluded.role, deleted_at = NULL
+            ", rusqlite::params![user_id, class_id, role])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    pub async fn get_membership(&self, user_id: usize, class_id: usize) -> Result<Option<Membership>> {
+        self.select_one(
+            format!("SELECT {} FROM user_classes WHERE user_id = ? AND class_id = ? AND deleted_at IS NULL", COLUMNS),
+            vec![id(user_id), id(class_id)], Membership::from_row
+        ).await
+    }
+    pub async fn list_memberships(&self, filter: MembershipFilter) -> Result<Vec<Membership>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("user_id = ?", filter.user_id, id);
+        conditions.add_opt("class_id = ?", filter.class_id, id);
+        conditions.add_opt("role = ?", filter.role, role);
+        conditions.not_deleted(filter.include_deleted);
+        self.select(
+            format!("SELECT {} FROM user_classes {} ORDER BY class_id, user_id", COLUMNS, conditions.sql()),
+            conditions.params, Membership::from_row
+        ).await
+    }
+    /// Classes the user is currently in
+    pub async fn get_memberships(&self, user_id: usize) -> Result<Vec<Membership>> {
+        self.list_memberships(MembershipFilter { user_id: Some(user_id), ..Default::default() }).await
+    }
+    /// Returns false if the user isn't in the class
+    pub async fn set_membership_role(&self, user_id: usize, class_id: usize, role: Role) -> Result<bool> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE user_classes
+                SET role = ?3
+                WHERE user_id = ?1 AND class_id = ?2 AND deleted_at IS NULL
+            ", rusqlite::params![user_id, class_id, role])
+        }).await?;
+        Ok(changed > 0)
+    }
+    pub async fn delete_membership(&self, user_id: usize, class_id: usize) -> Result<bool> {
+        self.soft_delete("user_classes", "user_id = ? AND class_id = ?", vec![id(user_id), id(class_id)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let alice = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let bob = db.insert_user("bob".to_string(), "hash".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+
+        db.insert_membership(alice, class, Role::Student).await.unwrap();
+        db.insert_membership(alice, other, Role::Student).await.unwrap();
+        db.insert_membership(bob, class, Role::Teacher).await.unwrap();
+        assert_eq!(db.get_memberships(alice).await.unwrap().len(), 2);
+        let teachers = db.list_memberships(MembershipFilter { class_id: Some(class), role: Some(Role::Teacher), ..Default::default() }).await.unwrap();
+        assert_eq!(teachers.iter().map(|m| m.user_id).collect::<Vec<_>>(), [bob]);
+
+        assert!(db.set_membership_role(alice, class, Role::Delegate).await.unwrap());
+        assert_eq!(db.get_membership(alice, class).await.unwrap().unwrap().role, Role::Delegate);
+
+        assert!(db.delete_membership(alice, other).await.unwrap());
+        assert!(!db.set_membership_role(alice, other, Role::Delegate).await.unwrap());
+        assert!(db.get_membership(alice, other).await.unwrap().is_none());
+        assert_eq!(db.get_memberships(alice).await.unwrap().len(), 1);
+
+        // Adding someone again restores the membership instead of duplicating it
+        db.delete_membership(bob, class).await.unwrap();
+        db.insert_membership(bob, class, Role::Delegate).await.unwrap();
+        let membership = db.get_membership(bob, class).await.unwrap().unwrap();
+        assert_eq!(membership.role, Role::Delegate);
+        assert_eq!(db.list_memberships(MembershipFilter { include_deleted: true, ..Default::default() }).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,284 @@
+use rusqlite::Row;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use anyhow::Result;
+use super::{Conditions, DB, Holiday, HolidayFilter, Lesson, LessonData, LessonFilter, Repeat, date, id};
+
+const EXCEPTION_COLUMNS: &str = "lesson_id, date, cancelled, start, end, room_id";
+
+/// Change to one occurrence of a lesson
+#[derive(Clone, Debug)]
+pub struct LessonException {
+    pub lesson_id: usize,
+    /// Day the occurrence was planned on
+    pub date: NaiveDate,
+    pub cancelled: bool,
+    /// New start and end of the occurrence if it was moved
+    pub moved_to: Option<(NaiveDateTime, NaiveDateTime)>,
+    /// Room replacing the rooms of the lesson for this occurrence
+    pub room_id: Option<usize>
+}
+impl LessonException {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let start: Option<NaiveDateTime> = row.get(3)?;
+        let end: Option<NaiveDateTime> = row.get(4)?;
+        Ok(Self {
+            lesson_id: row.get(0)?,
+            date: row.get(1)?,
+            cancelled: row.get(2)?,
+            moved_to: start.zip(end),
+            room_id: row.get(5)?
+        })
+    }
+}
+
+/// One occurrence of a lesson, with its exception applied
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+    pub lesson: Lesson,
+    /// Day the occurrence was planned on, even if it was moved
+    pub date: NaiveDate,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub cancelled: bool,
+    pub moved: bool,
+    /// Room replacing the rooms of the lesson for this occurrence
+    pub room_id: Option<usize>
+}
+
+/// Whether a lesson starting at `start` has an occurrence planned on `date`
+fn takes_place_on(start: NaiveDateTime, repeat: Option<Repeat>, date: NaiveDate) -> bool {
+    let first = start.date();
+    match repeat {
+        None => date == first,
+        Some(repeat) => first <= date && date <= repeat.until && (date - first).num_days() % (7 * repeat.weeks.max(1) as i64) == 0
+    }
+}
+
+/// Days of the occurrences of a lesson which may overlap `from`..`to`
+fn planned_dates(start: NaiveDateTime, end: NaiveDateTime, repeat: Option<Repeat>, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> Vec<NaiveDate> {
+    let first = start.date();
+    let (step, last) = match repeat {
+        None => (1, first),
+        Some(repeat) => (7 * repeat.weeks.max(1) as i64, repeat.until)
+    };
+    // The occurrences before these ones end before the range
+    let skip = from.map_or(0, |from| ((from - end).num_days() / step).max(0));
+    (skip..)
+        .map(|i| first + Duration::days(i * step))
+        .take_while(|date| *date <= last && to.is_none_or(|to| date.and_time(start.time()) < to))
+        .collect()
+}
+
+impl Lesson {
+    /// Whether the lesson has an occurrence planned on `date`, which can have an exception
+    pub fn takes_place_on(&self, date: NaiveDate) -> bool {
+        takes_place_on(self.start, self.repeat, date)
+    }
+}
+
+impl LessonData {
+    /// Start and end of the occurrences of the lesson as planned, without the ones during holidays
+    pub fn occurrences(&self, holidays: &[Holiday]) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        planned_dates(self.start, self.end, self.repeat, None, None)
+            .into_iter()
+            .filter(|date| !holidays.iter().any(|h| h.contains(*date)))
+            .map(|date| {
+                let start = date.and_time(self.start.time());
+                (start, start + (self.end - self.start))
+            })
+            .collect()
+    }
+}
+
+/// Occurrences of the lesson overlapping `from`..`to`.
+///
+/// The occurrences planned during holidays are left out, unless they were moved
+fn occurrences(lesson: &Lesson, exceptions: &[LessonException], holidays: &[Holiday], from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> Vec<Occurrence> {
+    let mut dates = planned_dates(lesson.start, lesson.end, lesson.repeat, from, to);
+    let exceptions = exceptions.iter()
+        .filter(|e| e.lesson_id == lesson.id && takes_place_on(lesson.start, lesson.repeat, e.date))
+        .collect::<Vec<_>>();
+    // Occurrences moved into the range from another week
+    for exception in exceptions.iter().filter(|e| e.moved_to.is_some()) {
+        if !dates.contains(&exception.date) {
+            dates.push(exception.date);
+        }
+    }
+
+    let duration = lesson.end - lesson.start;
+    dates.into_iter()
+        .filter_map(|date| {
+            let exception = exceptions.iter().find(|e| e.date == date);
+            let moved_to = exception.and_then(|e| e.moved_to);
+            if moved_to.is_none() && holidays.iter().any(|h| h.contains(date)) {
+                return None;
+            }
+            let (start, end) = match moved_to {
+                None => (date.and_time(lesson.start.time()), date.and_time(lesson.start.time()) + duration),
+                Some(moved_to) => moved_to
+            };
+            if from.is_some_and(|from| end <= from) || to.is_some_and(|to| start >= to) {
+                return None;
+            }
+            Some(Occurrence {
+                lesson: lesson.clone(),
+                date,
+                start,
+                end,
+                cancelled: exception.is_some_and(|e| e.cancelled),
+                moved: moved_to.is_some(),
+                room_id: exception.and_then(|e| e.room_id)
+            })
+        })
+        .collect()
+}
+
+impl DB {
+    /// Add or replace the exception of an occurrence
+    pub async fn set_lesson_exception(&self, exception: LessonException) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO lesson_exceptions (lesson_id, date, cancelled, start, end, room_id, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+                ON CONFLICT (lesson_id, date) DO UPDATE
+                SET cancelled = excluded.cancelled, start = excluded.start, end = excluded.end, room_id = excluded.room_id
+            ", rusqlite::params![
+                exception.lesson_id, exception.date, exception.cancelled,
+                exception.moved_to.map(|m| m.0), exception.moved_to.map(|m| m.1), exception.room_id
+            ])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    /// Exceptions of any of the lessons
+    pub async fn list_lesson_exceptions(&self, lesson_ids: Vec<usize>) -> Result<Vec<LessonException>> {
+        let mut conditions = Conditions::default();
+        conditions.add_in("lesson_id IN ({})", lesson_ids.into_iter().map(id).collect());
+        self.select(
+            format!("SELECT {} FROM lesson_exceptions {} ORDER BY lesson_id, date", EXCEPTION_COLUMNS, conditions.sql()),
+            conditions.params, LessonException::from_row
+        ).await
+    }
+    /// Remove the exception of an occurrence, so it takes place as planned. Returns false if there was none
+    pub async fn delete_lesson_exception(&self, lesson_id: usize, day: NaiveDate) -> Result<bool> {
+        let changed = self.execute("DELETE FROM lesson_exceptions WHERE lesson_id = ? AND date = ?".to_string(), vec![id(lesson_id), date(day)]).await?;
+        Ok(changed > 0)
+    }
+
+    /// Occurrences of the lessons matching the filter between its `from` and `to` dates, sorted by start
+    pub async fn list_occurrences(&self, filter: LessonFilter) -> Result<Vec<Occurrence>> {
+        let (from, to) = (filter.from, filter.to);
+        let lessons = self.list_lessons(filter).await?;
+        let exceptions = self.list_lesson_exceptions(lessons.iter().map(|l| l.id).collect()).await?;
+        let holidays = self.list_holidays(HolidayFilter {
+            from: from.map(|from| from.date()),
+            to: to.map(|to| to.date()),
+            ..Default::default()
+        }).await?;
+        let mut occurrences = lessons.iter()
+            .flat_map(|lesson| occurrences(lesson, &exceptions, &holidays, from, to))
+            .collect::<Vec<_>>();
+        occurrences.sort_by_key(|o| (o.start, o.lesson.id));
+        Ok(occurrences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{LessonData, Repeat};
+    use super::*;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 9, day).unwrap()
+    }
+    fn at(d: u32, hour: u32) -> NaiveDateTime {
+        day(d).and_hms_opt(hour, 0, 0).unwrap()
+    }
+    async fn list(db: &DB, from: NaiveDateTime, to: NaiveDateTime) -> Vec<(usize, NaiveDateTime)> {
+        db.list_occurrences(LessonFilter { from: Some(from), to: Some(to), ..Default::default() }).await.unwrap()
+            .into_iter()
+            .map(|o| (o.lesson.id, o.start))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recurring() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        // Every monday of september, and tuesdays of A weeks
+        let weekly = db.insert_lesson(LessonData {
+            type_id: maths,
+            start: at(4, 8),
+            end: at(4, 9),
+            repeat: Some(Repeat { weeks: 1, until: day(25) })
+        }).await.unwrap();
+        let week_a = db.insert_lesson(LessonData {
+            type_id: maths,
+            start: at(5, 10),
+            end: at(5, 12),
+            repeat: Some(Repeat { weeks: 2, until: day(30) })
+        }).await.unwrap();
+        let once = db.insert_lesson(LessonData { type_id: maths, start: at(6, 8), end: at(6, 9), repeat: None }).await.unwrap();
+
+        assert_eq!(list(&db, at(1, 0), at(30, 0)).await, [
+            (weekly, at(4, 8)), (week_a, at(5, 10)), (once, at(6, 8)),
+            (weekly, at(11, 8)),
+            (weekly, at(18, 8)), (week_a, at(19, 10)),
+            (weekly, at(25, 8))
+        ]);
+        // The occurrence still going on at the start of the range
+        assert_eq!(list(&db, at(19, 11), at(25, 8)).await, [(week_a, at(19, 10))]);
+
+        // Cancelled, moved to another week and in another room
+        let b12 = db.insert_room("B12".to_string()).await.unwrap();
+        db.set_lesson_exception(LessonException { lesson_id: weekly, date: day(11), cancelled: true, moved_to: None, room_id: None }).await.unwrap();
+        db.set_lesson_exception(LessonException {
+            lesson_id: weekly,
+            date: day(18),
+            cancelled: false,
+            moved_to: Some((at(20, 14), at(20, 15))),
+            room_id: Some(b12)
+        }).await.unwrap();
+        // Not an occurrence of the lesson
+        db.set_lesson_exception(LessonException { lesson_id: weekly, date: day(12), cancelled: true, moved_to: None, room_id: None }).await.unwrap();
+        let september = LessonFilter { from: Some(at(1, 0)), to: Some(at(30, 0)), ..Default::default() };
+        let occurrences = db.list_occurrences(september).await.unwrap();
+        let cancelled = occurrences.iter().find(|o| o.date == day(11)).unwrap();
+        assert!(cancelled.cancelled && !cancelled.moved);
+        let moved = occurrences.iter().find(|o| o.date == day(18)).unwrap();
+        assert_eq!((moved.start, moved.moved, moved.room_id), (at(20, 14), true, Some(b12)));
+        assert_eq!(occurrences.len(), 7);
+        assert_eq!(list(&db, at(20, 0), at(21, 0)).await, [(weekly, at(20, 14))]);
+        assert!(db.delete_lesson_exception(weekly, day(18)).await.unwrap());
+        assert!(!db.delete_lesson_exception(weekly, day(18)).await.unwrap());
+
+        // Holidays suppress the occurrences, except the moved ones
+        db.insert_holiday("Pont".to_string(), day(18), day(20)).await.unwrap();
+        db.set_lesson_exception(LessonException {
+            lesson_id: week_a,
+            date: day(19),
+            cancelled: false,
+            moved_to: Some((at(22, 10), at(22, 12))),
+            room_id: None
+        }).await.unwrap();
+        assert_eq!(list(&db, at(18, 0), at(25, 0)).await, [(week_a, at(22, 10))]);
+    }
+
+    #[tokio::test]
+    async fn moved_into_range() {
+        let db = DB::new(None).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let once = db.insert_lesson(LessonData { type_id: maths, start: at(6, 8), end: at(6, 9), repeat: None }).await.unwrap();
+        db.set_lesson_exception(LessonException {
+            lesson_id: once,
+            date: day(6),
+            cancelled: false,
+            moved_to: Some((at(13, 8), at(13, 9))),
+            room_id: None
+        }).await.unwrap();
+        assert_eq!(list(&db, at(11, 0), at(16, 0)).await, [(once, at(13, 8))]);
+        assert_eq!(list(&db, at(4, 0), at(9, 0)).await, []);
+        let since = LessonFilter { from: Some(at(11, 0)), ..Default::default() };
+        assert_eq!(db.list_lessons(since).await.unwrap().len(), 1);
+    }
+}
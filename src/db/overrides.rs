@@ -0,0 +1,77 @@
+use rusqlite::Row;
+use anyhow::Result;
+use super::{Conditions, DB, id};
+
+// The overrides are only written down for auditing, nothing reads them back on the site yet
+#[allow(dead_code)]
+const COLUMNS: &str = "lesson_id, user_id, reason, conflicts";
+
+/// A lesson saved by an admin despite its conflicts with other lessons
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct ConflictOverride {
+    pub lesson_id: usize,
+    /// `None` if the user was removed from the database
+    pub user_id: Option<usize>,
+    pub reason: String,
+    /// Description of the conflicts that were ignored
+    pub conflicts: String
+}
+impl ConflictOverride {
+    #[allow(dead_code)]
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            lesson_id: row.get(0)?,
+            user_id: row.get(1)?,
+            reason: row.get(2)?,
+            conflicts: row.get(3)?
+        })
+    }
+}
+
+impl DB {
+    pub async fn insert_conflict_override(&self, lesson_id: usize, user_id: usize, reason: String, conflicts: String) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO conflict_overrides (lesson_id, user_id, reason, conflicts, created_at)
+                VALUES (?1, ?2, ?3, ?4, datetime('now'))
+            ", rusqlite::params![lesson_id, user_id, reason, conflicts])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    /// Overrides of a lesson, or of all the lessons if `None`, newest first
+    #[allow(dead_code)]
+    pub async fn list_conflict_overrides(&self, lesson_id: Option<usize>) -> Result<Vec<ConflictOverride>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("lesson_id = ?", lesson_id, id);
+        self.select(
+            format!("SELECT {} FROM conflict_overrides {} ORDER BY created_at DESC, id DESC", COLUMNS, conditions.sql()),
+            conditions.params, ConflictOverride::from_row
+        ).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::db::LessonData;
+    use super::*;
+
+    #[tokio::test]
+    async fn overrides() {
+        let db = DB::new(None).await.unwrap();
+        let admin = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let start = NaiveDate::from_ymd_opt(2023, 9, 4).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let data = LessonData { type_id: maths, start, end: start + chrono::Duration::hours(1), repeat: None };
+        let lesson = db.insert_lesson(data.clone()).await.unwrap();
+        let other = db.insert_lesson(data).await.unwrap();
+
+        db.insert_conflict_override(lesson, admin, "Exam".to_string(), "1A".to_string()).await.unwrap();
+        db.insert_conflict_override(other, admin, "Exam".to_string(), "1B".to_string()).await.unwrap();
+        let overrides = db.list_conflict_overrides(Some(lesson)).await.unwrap();
+        assert_eq!(overrides.iter().map(|o| (o.user_id, o.reason.as_str(), o.conflicts.as_str())).collect::<Vec<_>>(), [(Some(admin), "Exam", "1A")]);
+        assert_eq!(db.list_conflict_overrides(None).await.unwrap()[0].lesson_id, other);
+    }
+}
@@ -0,0 +1,113 @@
+use rusqlite::Row;
+use anyhow::Result;
+use super::{Conditions, DB, id, text};
+
+const COLUMNS: &str = "id, name";
+
+#[derive(Clone, Debug)]
+pub struct Room {
+    pub id: usize,
+    pub name: String
+}
+impl Room {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?
+        })
+    }
+}
+
+/// Room where a lesson takes place
+#[derive(Clone, Debug)]
+pub struct LessonRoom {
+    pub lesson_id: usize,
+    pub room_id: usize
+}
+
+impl DB {
+    /// Fails if a room has the same name, ignoring the case
+    pub async fn insert_room(&self, name: String) -> Result<usize> {
+        let id = self.conn.call(|conn| {
+            conn.execute("
+                INSERT INTO rooms (name, created_at)
+                VALUES (?1, datetime('now'))
+            ", [name])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_room(&self, room_id: usize) -> Result<Option<Room>> {
+        self.select_one(format!("SELECT {} FROM rooms WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(room_id)], Room::from_row).await
+    }
+    pub async fn list_rooms(&self, include_deleted: bool) -> Result<Vec<Room>> {
+        let mut conditions = Conditions::default();
+        conditions.not_deleted(include_deleted);
+        self.select(
+            format!("SELECT {} FROM rooms {} ORDER BY name", COLUMNS, conditions.sql()),
+            conditions.params, Room::from_row
+        ).await
+    }
+    /// Returns false if the room doesn't exist
+    pub async fn rename_room(&self, room_id: usize, name: String) -> Result<bool> {
+        let changed = self.execute("UPDATE rooms SET name = ? WHERE id = ?".to_string(), vec![text(name), id(room_id)]).await?;
+        Ok(changed > 0)
+    }
+    pub async fn delete_room(&self, room_id: usize) -> Result<bool> {
+        self.soft_delete("rooms", "id = ?", vec![id(room_id)]).await
+    }
+
+    // lesson_rooms
+    /// Rooms of any of the lessons
+    pub async fn list_rooms_of_lessons(&self, lesson_ids: Vec<usize>) -> Result<Vec<LessonRoom>> {
+        let mut conditions = Conditions::default();
+        conditions.add_in("lesson_id IN ({})", lesson_ids.into_iter().map(id).collect());
+        conditions.not_deleted(false);
+        self.select(
+            format!("SELECT lesson_id, room_id FROM lesson_rooms {} ORDER BY lesson_id, room_id", conditions.sql()),
+            conditions.params, |row| Ok(LessonRoom { lesson_id: row.get(0)?, room_id: row.get(1)? })
+        ).await
+    }
+    pub async fn set_lesson_rooms(&self, lesson_id: usize, room_ids: Vec<usize>) -> Result<()> {
+        self.set_lesson_links("lesson_rooms", "room_id", lesson_id, room_ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::db::{LessonData, TrashKind};
+    use super::*;
+
+    async fn rooms(db: &DB, lesson_id: usize) -> Vec<usize> {
+        db.list_rooms_of_lessons(vec![lesson_id]).await.unwrap().iter().map(|r| r.room_id).collect()
+    }
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let b12 = db.insert_room("B12".to_string()).await.unwrap();
+        let a01 = db.insert_room("A01".to_string()).await.unwrap();
+        assert!(db.insert_room("b12".to_string()).await.is_err());
+        assert_eq!(db.list_rooms(false).await.unwrap().iter().map(|r| r.id).collect::<Vec<_>>(), [a01, b12]);
+        assert!(db.rename_room(b12, "B13".to_string()).await.unwrap());
+        assert_eq!(db.get_room(b12).await.unwrap().unwrap().name, "B13");
+
+        // Rooms of a lesson
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let start = NaiveDate::from_ymd_opt(2023, 9, 4).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let lesson = db.insert_lesson(LessonData { type_id: maths, start, end: start + chrono::Duration::hours(1), repeat: None }).await.unwrap();
+        db.set_lesson_rooms(lesson, vec![a01, b12]).await.unwrap();
+        assert_eq!(rooms(&db, lesson).await, [b12, a01]);
+        db.set_lesson_rooms(lesson, vec![b12]).await.unwrap();
+        assert_eq!(rooms(&db, lesson).await, [b12]);
+
+        // Deleting a room removes it from the lessons until it is restored
+        assert!(db.delete_room(b12).await.unwrap());
+        assert!(db.get_room(b12).await.unwrap().is_none());
+        assert!(rooms(&db, lesson).await.is_empty());
+        assert_eq!(db.list_rooms(true).await.unwrap().len(), 2);
+        assert!(db.restore_item(TrashKind::Room, b12).await.unwrap());
+        assert_eq!(rooms(&db, lesson).await, [b12]);
+    }
+}
@@ -0,0 +1,98 @@
+use rusqlite::OptionalExtension;
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use super::{DB, User};
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub expires_at: NaiveDateTime
+}
+
+impl DB {
+    pub async fn insert_session(&self, id: String, user_id: usize, expires_at: NaiveDateTime) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO sessions (id, user_id, created_at, expires_at)
+                VALUES (?1, ?2, datetime('now'), ?3)
+            ", rusqlite::params![id, user_id, expires_at])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    /// Get a session that hasn't expired, with its user
+    pub async fn get_session(&self, id: String) -> Result<Option<(Session, User)>> {
+        let session = self.conn.call(move |conn| {
+            conn.query_row("
+                SELECT sessions.expires_at,
+                    users.id, users.username, users.password_hash, users.role, users.created_at
+                FROM sessions
+                JOIN users ON users.id = sessions.user_id
+                WHERE sessions.id = ?1 AND sessions.expires_at > datetime('now') AND users.deleted_at IS NULL
+            ", [id], |row| {
+                Ok((Session {
+                    expires_at: row.get(0)?
+                }, User::from_row_at(row, 1)?))
+            }).optional()
+        }).await?;
+        Ok(session)
+    }
+    pub async fn renew_session(&self, id: String, expires_at: NaiveDateTime) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE sessions
+                SET expires_at = ?2
+                WHERE id = ?1
+            ", rusqlite::params![id, expires_at])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    pub async fn delete_session(&self, id: String) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                DELETE FROM sessions
+                WHERE id = ?1
+            ", [id])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    pub async fn delete_expired_sessions(&self) -> Result<()> {
+        self.conn.call(|conn| {
+            conn.execute("
+                DELETE FROM sessions
+                WHERE expires_at <= datetime('now')
+            ", ())?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use crate::db::TrashKind;
+    use super::*;
+
+    #[tokio::test]
+    async fn expiration() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let now = Utc::now().naive_utc();
+        db.insert_session("valid".to_string(), user, now + Duration::days(1)).await.unwrap();
+        db.insert_session("expired".to_string(), user, now - Duration::days(1)).await.unwrap();
+
+        let (_, session_user) = db.get_session("valid".to_string()).await.unwrap().unwrap();
+        assert_eq!(session_user.id, user);
+        assert!(db.get_session("expired".to_string()).await.unwrap().is_none());
+
+        db.delete_user(user).await.unwrap();
+        assert!(db.get_session("valid".to_string()).await.unwrap().is_none());
+        db.restore_item(TrashKind::User, user).await.unwrap();
+
+        db.delete_expired_sessions().await.unwrap();
+        db.delete_session("valid".to_string()).await.unwrap();
+        assert!(db.get_session("valid".to_string()).await.unwrap().is_none());
+    }
+}
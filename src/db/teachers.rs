@@ -0,0 +1,121 @@
+use rusqlite::Row;
+use anyhow::Result;
+use super::{Conditions, DB, id};
+
+const COLUMNS: &str = "id, name, user_id";
+
+#[derive(Clone, Debug)]
+pub struct Teacher {
+    pub id: usize,
+    pub name: String,
+    /// Account of the teacher on the site, if they have one
+    pub user_id: Option<usize>
+}
+impl Teacher {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            user_id: row.get(2)?
+        })
+    }
+}
+
+/// Teacher of a lesson, a lesson can have several of them
+#[derive(Clone, Debug)]
+pub struct LessonTeacher {
+    pub lesson_id: usize,
+    pub teacher_id: usize
+}
+
+impl DB {
+    /// Fails if another teacher has the same account
+    pub async fn insert_teacher(&self, name: String, user_id: Option<usize>) -> Result<usize> {
+        let id = self.conn.call(move |conn| {
+            conn.execute("
+                INSERT INTO teachers (name, user_id, created_at)
+                VALUES (?1, ?2, datetime('now'))
+            ", rusqlite::params![name, user_id])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn get_teacher(&self, teacher_id: usize) -> Result<Option<Teacher>> {
+        self.select_one(format!("SELECT {} FROM teachers WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(teacher_id)], Teacher::from_row).await
+    }
+    pub async fn list_teachers(&self, include_deleted: bool) -> Result<Vec<Teacher>> {
+        let mut conditions = Conditions::default();
+        conditions.not_deleted(include_deleted);
+        self.select(
+            format!("SELECT {} FROM teachers {} ORDER BY name, id", COLUMNS, conditions.sql()),
+            conditions.params, Teacher::from_row
+        ).await
+    }
+    /// Returns false if the teacher doesn't exist
+    pub async fn update_teacher(&self, teacher_id: usize, name: String, user_id: Option<usize>) -> Result<bool> {
+        let changed = self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE teachers
+                SET name = ?2, user_id = ?3
+                WHERE id = ?1
+            ", rusqlite::params![teacher_id, name, user_id])
+        }).await?;
+        Ok(changed > 0)
+    }
+    pub async fn delete_teacher(&self, teacher_id: usize) -> Result<bool> {
+        self.soft_delete("teachers", "id = ?", vec![id(teacher_id)]).await
+    }
+
+    // lesson_teachers
+    /// Teachers of any of the lessons
+    pub async fn list_teachers_of_lessons(&self, lesson_ids: Vec<usize>) -> Result<Vec<LessonTeacher>> {
+        let mut conditions = Conditions::default();
+        conditions.add_in("lesson_id IN ({})", lesson_ids.into_iter().map(id).collect());
+        conditions.not_deleted(false);
+        self.select(
+            format!("SELECT lesson_id, teacher_id FROM lesson_teachers {} ORDER BY lesson_id, teacher_id", conditions.sql()),
+            conditions.params, |row| Ok(LessonTeacher { lesson_id: row.get(0)?, teacher_id: row.get(1)? })
+        ).await
+    }
+    pub async fn set_lesson_teachers(&self, lesson_id: usize, teacher_ids: Vec<usize>) -> Result<()> {
+        self.set_lesson_links("lesson_teachers", "teacher_id", lesson_id, teacher_ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::db::{LessonData, LessonFilter, TrashKind};
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("bob".to_string(), "hash".to_string()).await.unwrap();
+        let dupont = db.insert_teacher("M. Dupont".to_string(), Some(user)).await.unwrap();
+        let martin = db.insert_teacher("Mme Martin".to_string(), None).await.unwrap();
+        assert!(db.insert_teacher("Bob".to_string(), Some(user)).await.is_err());
+        assert_eq!(db.get_teacher(dupont).await.unwrap().unwrap().user_id, Some(user));
+        assert!(db.update_teacher(martin, "Mme Martin".to_string(), None).await.unwrap());
+
+        // Co-teaching
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let start = NaiveDate::from_ymd_opt(2023, 9, 4).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let data = LessonData { type_id: maths, start, end: start + chrono::Duration::hours(1), repeat: None };
+        let lesson = db.insert_lesson(data.clone()).await.unwrap();
+        let other = db.insert_lesson(data).await.unwrap();
+        db.set_lesson_teachers(lesson, vec![dupont, martin]).await.unwrap();
+        db.set_lesson_teachers(other, vec![martin]).await.unwrap();
+        let teachers = db.list_teachers_of_lessons(vec![lesson, other]).await.unwrap();
+        assert_eq!(teachers.iter().map(|t| (t.lesson_id, t.teacher_id)).collect::<Vec<_>>(), [(lesson, dupont), (lesson, martin), (other, martin)]);
+        let of_teacher = |teacher_id| LessonFilter { teacher_id: Some(teacher_id), ..Default::default() };
+        assert_eq!(db.list_lessons(of_teacher(dupont)).await.unwrap().iter().map(|l| l.id).collect::<Vec<_>>(), [lesson]);
+        assert_eq!(db.list_lessons(of_teacher(martin)).await.unwrap().len(), 2);
+
+        assert!(db.delete_teacher(martin).await.unwrap());
+        assert_eq!(db.list_teachers(false).await.unwrap().len(), 1);
+        assert!(db.list_lessons(of_teacher(martin)).await.unwrap().is_empty());
+        assert!(db.restore_item(TrashKind::Teacher, martin).await.unwrap());
+        assert_eq!(db.list_lessons(of_teacher(martin)).await.unwrap().len(), 2);
+    }
+}
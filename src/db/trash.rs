@@ -0,0 +1,247 @@
+use rusqlite::Row;
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use super::{DB, datetime, id};
+
+/// Kind of the rows that can be restored from the trash
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrashKind {
+    Class,
+    User,
+    Homework,
+    LessonType,
+    Lesson,
+    Holiday,
+    Room,
+    Teacher
+}
+impl TrashKind {
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Class => "classes",
+            Self::User => "users",
+            Self::Homework => "homeworks",
+            Self::LessonType => "lesson_types",
+            Self::Lesson => "lessons",
+            Self::Holiday => "holidays",
+            Self::Room => "rooms",
+            Self::Teacher => "teachers"
+        }
+    }
+    pub fn parse(table: &str) -> Option<Self> {
+        match table {
+            "classes" => Some(Self::Class),
+            "users" => Some(Self::User),
+            "homeworks" => Some(Self::Homework),
+            "lesson_types" => Some(Self::LessonType),
+            "lessons" => Some(Self::Lesson),
+            "holidays" => Some(Self::Holiday),
+            "rooms" => Some(Self::Room),
+            "teachers" => Some(Self::Teacher),
+            _ => None
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Class => "Classe",
+            Self::User => "Utilisateur",
+            Self::Homework => "Devoir",
+            Self::LessonType => "Matière",
+            Self::Lesson => "Cours",
+            Self::Holiday => "Vacances",
+            Self::Room => "Salle",
+            Self::Teacher => "Professeur"
+        }
+    }
+}
+
+/// A soft deleted row
+#[derive(Clone, Debug)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    pub id: usize,
+    pub name: String,
+    pub deleted_at: NaiveDateTime
+}
+impl TrashItem {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let table: String = row.get(0)?;
+        Ok(Self {
+            // The tables are the ones written in `list_trash`
+            kind: TrashKind::parse(&table).expect("Unknown table in the trash"),
+            id: row.get(1)?,
+            name: row.get(2)?,
+            deleted_at: row.get(3)?
+        })
+    }
+}
+
+impl DB {
+    /// Deleted rows, newest first.
+    ///
+    /// Rows deleted with the row they depend on are not listed, they are restored with it
+    pub async fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        self.select("
+            SELECT 'classes', id, name, deleted_at FROM classes
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'users', id, username, deleted_at FROM users
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'homeworks', id, name, deleted_at FROM homeworks
+            WHERE deleted_at IS NOT NULL
+                AND class_id NOT IN (SELECT id FROM classes WHERE deleted_at = homeworks.deleted_at)
+            UNION ALL
+            SELECT 'lesson_types', id, name, deleted_at FROM lesson_types
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'lessons', id, (SELECT name FROM lesson_types WHERE id = type_id) || ' ' || start, deleted_at FROM lessons
+            WHERE deleted_at IS NOT NULL
+                AND type_id NOT IN (SELECT id FROM lesson_types WHERE deleted_at = lessons.deleted_at)
+            UNION ALL
+            SELECT 'holidays', id, name, deleted_at FROM holidays
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'rooms', id, name, deleted_at FROM rooms
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'teachers', id, name, deleted_at FROM teachers
+            WHERE deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+        ".to_string(), Vec::new(), TrashItem::from_row).await
+    }
+    /// Restore a row from the trash, with the rows deleted with it. Returns false if it wasn't deleted
+    pub async fn restore_item(&self, kind: TrashKind, item_id: usize) -> Result<bool> {
+        self.restore(kind.table(), "id = ?", vec![id(item_id)]).await
+    }
+    /// Permanently remove the rows deleted before `before`, and the rows depending on them.
+    ///
+    /// Returns the number of rows removed
+    pub async fn purge_deleted(&self, before: NaiveDateTime) -> Result<usize> {
+        let before = datetime(before);
+        let removed = self.conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            let mut removed = 0;
+            // Parents first, so the rows left without a parent can be removed after them
+            for sql in [
+                "DELETE FROM classes WHERE deleted_at < ?1",
+                "DELETE FROM users WHERE deleted_at < ?1",
+                "DELETE FROM lesson_types WHERE deleted_at < ?1",
+                "DELETE FROM holidays WHERE deleted_at < ?1",
+                "DELETE FROM rooms WHERE deleted_at < ?1",
+                "DELETE FROM teachers WHERE deleted_at < ?1",
+                "DELETE FROM lessons WHERE deleted_at < ?1 OR type_id NOT IN (SELECT id FROM lesson_types)",
+                "DELETE FROM homeworks WHERE deleted_at < ?1 OR class_id NOT IN (SELECT id FROM classes)",
+                "DELETE FROM user_classes WHERE deleted_at < ?1
+                    OR user_id NOT IN (SELECT id FROM users) OR class_id NOT IN (SELECT id FROM classes)",
+                "DELETE FROM lesson_classes WHERE deleted_at < ?1
+                    OR lesson_id NOT IN (SELECT id FROM lessons) OR class_id NOT IN (SELECT id FROM classes)",
+                "DELETE FROM lesson_rooms WHERE deleted_at < ?1
+                    OR lesson_id NOT IN (SELECT id FROM lessons) OR room_id NOT IN (SELECT id FROM rooms)",
+                "DELETE FROM lesson_teachers WHERE deleted_at < ?1
+                    OR lesson_id NOT IN (SELECT id FROM lessons) OR teacher_id NOT IN (SELECT id FROM teachers)"
+            ] {
+                removed += tx.execute(sql, [&before])?;
+            }
+            removed += tx.execute("DELETE FROM lesson_exceptions WHERE lesson_id NOT IN (SELECT id FROM lessons)", ())?;
+            removed += tx.execute("DELETE FROM conflict_overrides WHERE lesson_id NOT IN (SELECT id FROM lessons)", ())?;
+            removed += tx.execute("DELETE FROM membership_changes WHERE class_id NOT IN (SELECT id FROM classes)", ())?;
+            // Optional references are kept empty
+            tx.execute("UPDATE homeworks SET created_by = NULL WHERE created_by NOT IN (SELECT id FROM users)", ())?;
+            tx.execute("UPDATE homeworks SET lesson_id = NULL WHERE lesson_id NOT IN (SELECT id FROM lessons)", ())?;
+            tx.execute("UPDATE conflict_overrides SET user_id = NULL WHERE user_id NOT IN (SELECT id FROM users)", ())?;
+            tx.execute("UPDATE membership_changes SET actor_id = NULL WHERE actor_id NOT IN (SELECT id FROM users)", ())?;
+            tx.execute("UPDATE membership_changes SET user_id = NULL WHERE user_id NOT IN (SELECT id FROM users)", ())?;
+            tx.execute("UPDATE teachers SET user_id = NULL WHERE user_id NOT IN (SELECT id FROM users)", ())?;
+            tx.execute("UPDATE lesson_exceptions SET room_id = NULL WHERE room_id NOT IN (SELECT id FROM rooms)", ())?;
+            tx.execute("DELETE FROM sessions WHERE user_id NOT IN (SELECT id FROM users)", ())?;
+            tx.commit()?;
+            Ok(removed)
+        }).await?;
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, Utc};
+    use crate::db::{ClassFilter, HomeworkData, HomeworkFilter, LessonData, LessonFilter, MembershipFilter, Role};
+    use super::*;
+
+    fn homework(class_id: usize) -> HomeworkData {
+        HomeworkData {
+            name: "Maths".to_string(),
+            description: String::new(),
+            class_id,
+            lesson_id: None,
+            due_date: NaiveDate::from_ymd_opt(2023, 9, 4).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn cascade() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+        db.insert_membership(user, class, Role::Student).await.unwrap();
+        let kept = db.insert_homework(homework(class), user).await.unwrap();
+        let deleted_before = db.insert_homework(homework(class), user).await.unwrap();
+        db.insert_homework(homework(other), user).await.unwrap();
+
+        db.delete_homework(deleted_before).await.unwrap();
+        db.delete_class(class).await.unwrap();
+        let in_class = HomeworkFilter { class_id: Some(class), ..Default::default() };
+        assert!(db.list_homeworks(in_class.clone()).await.unwrap().is_empty());
+        assert!(db.get_homework(kept).await.unwrap().is_none());
+        assert!(db.get_memberships(user).await.unwrap().is_empty());
+        assert_eq!(db.list_homeworks(HomeworkFilter::default()).await.unwrap().len(), 1);
+
+        // The homeworks of the class are restored with it, not the one deleted before
+        let trash = db.list_trash().await.unwrap();
+        assert_eq!(trash.iter().map(|i| (i.kind, i.id)).collect::<Vec<_>>(), [(TrashKind::Class, class), (TrashKind::Homework, deleted_before)]);
+        assert!(db.restore_item(TrashKind::Class, class).await.unwrap());
+        assert_eq!(db.list_homeworks(in_class).await.unwrap().iter().map(|h| h.id).collect::<Vec<_>>(), [kept]);
+        assert_eq!(db.get_memberships(user).await.unwrap().len(), 1);
+        assert!(!db.restore_item(TrashKind::Class, class).await.unwrap());
+
+        // Through several levels
+        let maths = db.insert_lesson_type("Maths".to_string()).await.unwrap();
+        let start = NaiveDate::from_ymd_opt(2023, 9, 4).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let lesson = db.insert_lesson(LessonData { type_id: maths, start, end: start + Duration::hours(1), repeat: None }).await.unwrap();
+        db.set_lesson_classes(lesson, vec![other]).await.unwrap();
+        db.delete_lesson_type(maths).await.unwrap();
+        assert!(db.list_lessons(LessonFilter::default()).await.unwrap().is_empty());
+        assert!(db.list_lesson_classes(lesson, false).await.unwrap().is_empty());
+        assert_eq!(db.list_trash().await.unwrap()[0].kind, TrashKind::LessonType);
+        db.restore_item(TrashKind::LessonType, maths).await.unwrap();
+        assert_eq!(db.list_lessons(LessonFilter { class_id: Some(other), ..Default::default() }).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn purge() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let class = db.insert_class("1A".to_string()).await.unwrap();
+        let other = db.insert_class("1B".to_string()).await.unwrap();
+        db.insert_membership(user, class, Role::Student).await.unwrap();
+        db.insert_homework(homework(class), user).await.unwrap();
+        let homework = db.insert_homework(homework(other), user).await.unwrap();
+        db.delete_class(class).await.unwrap();
+        db.delete_user(user).await.unwrap();
+
+        // Nothing is old enough
+        assert_eq!(db.purge_deleted(Utc::now().naive_utc() - Duration::days(1)).await.unwrap(), 0);
+        assert_eq!(db.list_trash().await.unwrap().len(), 2);
+
+        // The class, its homework, the user and their membership
+        assert_eq!(db.purge_deleted(Utc::now().naive_utc() + Duration::seconds(1)).await.unwrap(), 4);
+        assert!(db.list_trash().await.unwrap().is_empty());
+        assert_eq!(db.list_classes(ClassFilter { include_deleted: true }).await.unwrap().len(), 1);
+        assert!(db.list_memberships(MembershipFilter { include_deleted: true, ..Default::default() }).await.unwrap().is_empty());
+        let homeworks = db.list_homeworks(HomeworkFilter { include_deleted: true, ..Default::default() }).await.unwrap();
+        assert_eq!(homeworks.iter().map(|h| h.id).collect::<Vec<_>>(), [homework]);
+        // It isn't attributed to the purged user anymore
+        assert!(db.list_homeworks(HomeworkFilter { created_by: Some(user), include_deleted: true, ..Default::default() }).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use rusqlite::Row;
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use super::{Conditions, DB, Role, id, role, text};
+
+const COLUMNS: &str = "id, username, password_hash, role, created_at";
+
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: usize,
+    pub name: String,
+    pub password_hash: String,
+    pub role: Role,
+    pub created_at: NaiveDateTime
+}
+impl User {
+    /// Read a user from the columns `COLUMNS` starting at `offset`
+    pub(super) fn from_row_at(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(offset)?,
+            name: row.get(offset + 1)?,
+            password_hash: row.get(offset + 2)?,
+            role: row.get(offset + 3)?,
+            created_at: row.get(offset + 4)?
+        })
+    }
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Self::from_row_at(row, 0)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UserFilter {
+    pub role: Option<Role>,
+    pub include_deleted: bool
+}
+
+impl DB {
+    pub async fn insert_user(&self, name: String, password_hash: String) -> Result<usize> {
+        let id = self.conn.call(|conn| {
+            conn.execute("
+                INSERT INTO users (username, password_hash, created_at)
+                VALUES (?1, ?2, datetime('now'))
+            ", [name, password_hash])?;
+            Ok(conn.last_insert_rowid())
+        }).await?;
+        Ok(id as usize)
+    }
+    pub async fn update_user_password_hash(&self, id: usize, password_hash: String) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE users
+                SET password_hash = ?2
+                WHERE id = ?1
+            ", rusqlite::params![id, password_hash])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    pub async fn set_user_role(&self, id: usize, role: Role) -> Result<()> {
+        self.conn.call(move |conn| {
+            conn.execute("
+                UPDATE users
+                SET role = ?2
+                WHERE id = ?1
+            ", rusqlite::params![id, role])?;
+            Ok(())
+        }).await?;
+        Ok(())
+    }
+    /// Returns false if the user doesn't exist
+    #[allow(dead_code)] // Accounts can't be renamed or removed from the site yet
+    pub async fn rename_user(&self, user_id: usize, name: String) -> Result<bool> {
+        let changed = self.execute("UPDATE users SET username = ? WHERE id = ?".to_string(), vec![text(name), id(user_id)]).await?;
+        Ok(changed > 0)
+    }
+    /// Get a user that isn't deleted by their name, ignoring the case
+    pub async fn get_user_by_name(&self, name: String) -> Result<Option<User>> {
+        self.select_one(
+            format!("SELECT {} FROM users WHERE username = ? COLLATE NOCASE AND deleted_at IS NULL", COLUMNS),
+            vec![text(name)], User::from_row
+        ).await
+    }
+    pub async fn get_user(&self, user_id: usize) -> Result<Option<User>> {
+        self.select_one(format!("SELECT {} FROM users WHERE id = ? AND deleted_at IS NULL", COLUMNS), vec![id(user_id)], User::from_row).await
+    }
+    pub async fn list_users(&self, filter: UserFilter) -> Result<Vec<User>> {
+        let mut conditions = Conditions::default();
+        conditions.add_opt("role = ?", filter.role, role);
+        conditions.not_deleted(filter.include_deleted);
+        self.select(format!("SELECT {} FROM users {} ORDER BY username", COLUMNS, conditions.sql()), conditions.params, User::from_row).await
+    }
+    #[allow(dead_code)]
+    pub async fn delete_user(&self, user_id: usize) -> Result<bool> {
+        self.soft_delete("users", "id = ?", vec![id(user_id)]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{TrashKind, is_unique_violation};
+    use super::*;
+
+    #[tokio::test]
+    async fn crud() {
+        let db = DB::new(None).await.unwrap();
+        let alice = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        let bob = db.insert_user("bob".to_string(), "hash".to_string()).await.unwrap();
+
+        let user = db.get_user(alice).await.unwrap().unwrap();
+        assert_eq!(user.name, "alice");
+        assert_eq!(user.role, Role::Student);
+        assert!(db.get_user(bob + 1).await.unwrap().is_none());
+
+        db.update_user_password_hash(alice, "new hash".to_string()).await.unwrap();
+        db.set_user_role(bob, Role::Teacher).await.unwrap();
+        assert!(db.rename_user(bob, "robert".to_string()).await.unwrap());
+        assert_eq!(db.get_user(alice).await.unwrap().unwrap().password_hash, "new hash");
+        let bob_user = db.get_user_by_name("robert".to_string()).await.unwrap().unwrap();
+        assert_eq!((bob_user.id, bob_user.role), (bob, Role::Teacher));
+
+        let teachers = db.list_users(UserFilter { role: Some(Role::Teacher), ..Default::default() }).await.unwrap();
+        assert_eq!(teachers.iter().map(|u| u.id).collect::<Vec<_>>(), [bob]);
+
+        assert!(db.delete_user(alice).await.unwrap());
+        assert!(!db.delete_user(alice).await.unwrap());
+        assert!(db.get_user_by_name("alice".to_string()).await.unwrap().is_none());
+        assert!(db.get_user(alice).await.unwrap().is_none());
+        assert_eq!(db.list_users(UserFilter::default()).await.unwrap().len(), 1);
+        assert_eq!(db.list_users(UserFilter { include_deleted: true, ..Default::default() }).await.unwrap().len(), 2);
+
+        assert!(db.restore_item(TrashKind::User, alice).await.unwrap());
+        assert!(db.get_user_by_name("alice".to_string()).await.unwrap().is_some());
+
+        // The names are unique ignoring the case
+        let taken = db.insert_user("Alice".to_string(), "hash".to_string()).await.unwrap_err();
+        assert!(is_unique_violation(&taken));
+        assert!(is_unique_violation(&db.rename_user(bob, "ALICE".to_string()).await.unwrap_err()));
+        assert!(!is_unique_violation(&anyhow::anyhow!("Other error")));
+    }
+
+    #[tokio::test]
+    async fn name_lookup_ignores_the_case() {
+        let db = DB::new(None).await.unwrap();
+        let alice = db.insert_user("Alice".to_string(), "hash".to_string()).await.unwrap();
+        let user = db.get_user_by_name("alice".to_string()).await.unwrap().unwrap();
+        assert_eq!((user.id, user.name.as_str()), (alice, "Alice"));
+        assert!(db.get_user_by_name("ALICE".to_string()).await.unwrap().is_some());
+        assert!(db.get_user_by_name("alicia".to_string()).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use notify::{RecursiveMode, Watcher};
+use tokio::{io::{AsyncWrite, AsyncWriteExt}, sync::{broadcast, mpsc}};
+use crate::assets::{Assets, SharedAssets};
+
+/// Url of the server-sent events stream telling the pages to reload
+pub const EVENTS_PATH: &str = "/dev/reload";
+
+/// Injected in the pages in dev mode
+pub const RELOAD_SCRIPT: &str = r#"<script>new EventSource("/dev/reload").onmessage = () => location.reload();</script>"#;
+
+/// Watch `public/` and reload the assets when a file changes.
+///
+/// Returns the channel on which an event is sent after each reload.
+pub fn watch(assets: Arc<SharedAssets>) -> notify::Result<broadcast::Sender<()>> {
+    let (reloads, _) = broadcast::channel(16);
+    let (changes_tx, mut changes) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        match res {
+            Ok(event) if event.kind.is_access() => {},
+            Ok(_) => {
+                let _ = changes_tx.send(());
+            },
+            Err(e) => eprintln!("Error watching files: {}", e)
+        }
+    })?;
+    watcher.watch(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("public"), RecursiveMode::Recursive)?;
+
+    let reloads_tx = reloads.clone();
+    tokio::spawn(async move {
+        // Moved here so it's not dropped
+        let _watcher = watcher;
+        while changes.recv().await.is_some() {
+            // Editors often write several times, wait for them to finish
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            while changes.try_recv().is_ok() {}
+
+            println!("Files changed, reloading");
+            let previous = assets.get();
+            match Assets::load(Some(&previous)).await {
+                Ok(new) => assets.set(new),
+                Err(e) => {
+                    // Keep serving the last assets, with the error shown on the pages
+                    eprintln!("Error reloading the files: {:#}", e);
+                    assets.set(Assets { errors: vec![format!("{:#}", e)], ..(*previous).clone() });
+                }
+            }
+            let _ = reloads_tx.send(());
+        }
+    });
+
+    Ok(reloads)
+}
+
+/// Keep the connection open and send an event each time the assets are reloaded
+pub async fn send_events<S: AsyncWrite + Unpin>(mut stream: S, mut reloads: broadcast::Receiver<()>) -> std::io::Result<()> {
+    stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n").await?;
+    stream.flush().await?;
+    loop {
+        match reloads.recv().await {
+            Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                stream.write_all(b"data: reload\n\n").await?;
+                stream.flush().await?;
+            },
+            Err(broadcast::error::RecvError::Closed) => return Ok(())
+        }
+    }
+}
@@ -1,25 +1,59 @@
+
 extern crate time;
-extern crate argon2;
 
-use std::{path::PathBuf, sync::Arc, borrow::Borrow, fs::read_to_string, collections::HashMap};
-use db::DB;
-use tokio::{net::TcpListener, io::{AsyncWriteExt, AsyncWrite}};
-use minijinja::{Environment, context, value::StructObject};
+use std::{sync::Arc, borrow::Borrow, collections::HashMap, time::Duration};
+use assets::{Assets, SharedAssets};
+use conflicts::Conflict;
+use db::{DB, Homework, Role, TrashItem, User};
+use password::HashParams;
+use permissions::Permissions;
+use request::{Limits, ReadError, RequestReader};
+use router::Router;
+use tokio::{net::{TcpListener, TcpStream}, io::{AsyncWriteExt, AsyncWrite}, sync::broadcast, time::timeout};
+use minijinja::value::StructObject;
 use http_bytes::{http, http::StatusCode};
-use walkdir::WalkDir;
 use anyhow::Error;
 
+// The Pronote client isn't called by the server yet, only by its own tests
+#[allow(dead_code)]
 mod api;
+mod assets;
+mod conflicts;
+mod dates;
 mod db;
+mod hot_reload;
+mod migrations;
+mod password;
+mod permissions;
+// Only used by the client above
+#[allow(dead_code)]
+mod pronote;
+mod request;
+mod router;
+mod routes;
+mod scss;
+mod sessions;
+mod timetable;
 
 const HOST: &str = "127.0.0.1:8080";
+/// Days after which deleted rows are permanently removed
+const DEFAULT_RETENTION_DAYS: i64 = 30;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let argv = std::env::args().collect::<Vec<_>>();
     let dev_mode = argv.contains(&"--dev".to_string());
     
-    let env = Arc::new(create_env().await);
+    let assets = Assets::load(None).await?;
+    if !dev_mode && !assets.errors.is_empty() {
+        return Err(anyhow::anyhow!("Failed to compile the templates or the stylesheets"));
+    }
+    let assets = Arc::new(SharedAssets::new(assets));
+    let reloads = if dev_mode {
+        Some(hot_reload::watch(assets.clone())?)
+    } else {
+        None
+    };
 
     let db_path = if dev_mode {
         Some("test.db")
@@ -27,7 +61,83 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
-    let db = Arc::new(DB::new(db_path).await);
+    let db = Arc::new(DB::new(db_path).await?);
+
+    let mut config = ConnectionConfig::default();
+    if let Some(size) = arg_value(&argv, "--max-head-size") {
+        config.limits.max_head_size = size.parse()?;
+    }
+    if let Some(size) = arg_value(&argv, "--max-body-size") {
+        config.limits.max_body_size = size.parse()?;
+    }
+    if let Some(secs) = arg_value(&argv, "--keep-alive-timeout") {
+        config.idle_timeout = Duration::from_secs(secs.parse()?);
+    }
+    if let Some(secs) = arg_value(&argv, "--request-timeout") {
+        config.request_timeout = Duration::from_secs(secs.parse()?);
+    }
+    if let Some(max) = arg_value(&argv, "--max-requests") {
+        config.max_requests = max.parse()?;
+    }
+
+    let mut hash_params = HashParams::default();
+    if let Some(mem_cost) = arg_value(&argv, "--argon2-mem-cost") {
+        hash_params.mem_cost = mem_cost.parse()?;
+    }
+    if let Some(time_cost) = arg_value(&argv, "--argon2-time-cost") {
+        hash_params.time_cost = time_cost.parse()?;
+    }
+    if let Some(lanes) = arg_value(&argv, "--argon2-lanes") {
+        hash_params.lanes = lanes.parse()?;
+    }
+    password::set_params(hash_params);
+
+    if let Some(name) = arg_value(&argv, "--admin") {
+        match db.get_user_by_name(name.to_string()).await? {
+            None => return Err(anyhow::anyhow!("Unknown user : {}", name)),
+            Some(user) => db.set_user_role(user.id, Role::Admin).await?
+        }
+    }
+
+    // Remove expired sessions every hour
+    let sessions_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = sessions_db.delete_expired_sessions().await {
+                eprintln!("Error removing expired sessions: {}", e);
+            }
+        }
+    });
+
+    // Permanently remove what has been in the trash for too long, every day
+    let retention_days = match arg_value(&argv, "--retention-days") {
+        None => DEFAULT_RETENTION_DAYS,
+        Some(days) => days.parse()?
+    };
+    let purge_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        loop {
+            interval.tick().await;
+            let before = chrono::Utc::now().naive_utc() - chrono::Duration::days(retention_days);
+            match purge_db.purge_deleted(before).await {
+                Ok(0) => {},
+                Ok(removed) => println!("Purged {} deleted rows", removed),
+                Err(e) => eprintln!("Error purging deleted rows: {}", e)
+            }
+        }
+    });
+
+    let server = Arc::new(Server {
+        router: routes::create_router(dev_mode),
+        assets,
+        db,
+        config,
+        dev_mode,
+        reloads
+    });
 
     let tcp = TcpListener::bind(HOST).await?;
 
@@ -36,123 +146,194 @@ async fn main() -> anyhow::Result<()> {
             Err(e) => eprintln!("Error accepting connection: {}", e),
             Ok((stream, addr)) => {
                 println!("Got connection from: {}", addr);
-                let env = if dev_mode {
-                    // Reload environment
-                    Arc::new(create_env().await)
-                } else {
-                    env.clone()
-                };
-                let db = db.clone();
-                let dev_mode = dev_mode.clone();
+                let server = server.clone();
                 tokio::spawn(async move {
-                    stream.readable().await.unwrap();
-                    let mut buffer = [0; 1024];
-                    stream.try_read(&mut buffer).unwrap();
-
-                    let mut headers = [httparse::EMPTY_HEADER; 64];
-                    let mut header_req = httparse::Request::new(&mut headers);
-                    if let Err(e) = header_req.parse(buffer.as_ref()) {
-                        eprintln!("Error parsing request: {}", e);
-                        let res = http_bytes::Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .body(())
-                            .unwrap();
-                        if let Err(e) = write_empty_response(res, stream).await {
-                            eprintln!("Error writing response: {}", e);
-                        };
-                        return;
-                    }
-
-                    let body = match std::str::from_utf8(&buffer) {
-                        Err(_) => None,
-                        Ok(b) => match b.split_once("\r\n\r\n") {
-                            None => None,
-                            Some((_, b)) => Some(b)
-                        }
-                    };
-
-                    let req = http::request::Builder::new()
-                        .method(header_req.method.unwrap())
-                        .uri(header_req.path.unwrap())
-                        .version(http::Version::HTTP_11)
-                        .body(body.map(|b| b.to_string()))
-                        .unwrap();
-
-                    if dev_mode && req.uri().path() == "/debug" {
-                        println!("{:#?}", req);
-                        println!("{:#?}", std::str::from_utf8(&buffer));
-                        return
-                    }
-
-                    match handle_connection(&req, env, db, dev_mode).await {
-                        Ok(Some(res)) => {
-                            if let Err(e) = write_response(res, stream).await {
-                                eprintln!("Error writing response: {}", e);
-                            }
-                        },
-                        Ok(None) => {},
-                        Err(HandleError::InternalServerError(e)) => {
-                            eprintln!("Internal server error: {}", e);
-                            let res = http_bytes::Response::builder()
-                                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                .body(b"Internal server error".to_vec())
-                                .unwrap();
-                            if let Err(e) = write_response(res, stream).await {
-                                eprintln!("Error writing response: {}", e);
-                            };
-                        },
-                        Err(HandleError::BadRequest) => {
-                            println!("Bad request");
-                            let res = http_bytes::Response::builder()
-                                .status(StatusCode::BAD_REQUEST)
-                                .body(b"Bad request".to_vec())
-                                .unwrap();
-                            if let Err(e) = write_response(res, stream).await {
-                                eprintln!("Error writing response: {}", e);
-                            };
-                        },
-                        Err(HandleError::NotFound) => {
-                            println!("Not found");
-                            let res = http_bytes::Response::builder()
-                                .status(StatusCode::NOT_FOUND)
-                                .body(b"Not found".to_vec())
-                                .unwrap();
-                            if let Err(e) = write_response(res, stream).await {
-                                eprintln!("Error writing response: {}", e);
-                            };
-                        }
-                    }
+                    handle_stream(stream, server).await;
                 });
             }
         }
     }
 }
 
-async fn create_env() -> Environment<'static> {
-    let mut env = Environment::new();
-    let template_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("public");
-    for entry in WalkDir::new(&template_path) {
-        let entry = entry.unwrap();
-        if entry.file_type().is_file() {
-            let path = entry.path();
-            if path.file_name().unwrap() == ".DS_Store" {
-                continue;
+/// State shared by all the connections
+struct Server {
+    router: Router,
+    assets: Arc<SharedAssets>,
+    db: Arc<DB>,
+    config: ConnectionConfig,
+    dev_mode: bool,
+    /// Sends an event each time the assets are reloaded (dev mode only)
+    reloads: Option<broadcast::Sender<()>>
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ConnectionConfig {
+    limits: Limits,
+    /// Time to wait for the next request before closing the connection
+    idle_timeout: Duration,
+    /// Time to receive the whole request once it started (answered with 408)
+    request_timeout: Duration,
+    /// Number of requests served on a connection before closing it
+    max_requests: usize
+}
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            limits: Limits::default(),
+            idle_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(30),
+            max_requests: 100
+        }
+    }
+}
+
+/// Check if the client wants to keep the connection open after this request
+fn wants_keep_alive<T>(req: &http::Request<T>) -> bool {
+    let mut keep_alive = req.version() != http::Version::HTTP_10;
+    for value in req.headers().get_all(http::header::CONNECTION).iter() {
+        for token in value.to_str().unwrap_or("").split(',') {
+            let token = token.trim();
+            if token.eq_ignore_ascii_case("close") {
+                return false;
+            } else if token.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
             }
-            let path = path.strip_prefix(&template_path).unwrap();
-            let mut path = "/".to_string() + path.to_str().unwrap();
-            if path.ends_with(".j2") {
-                path = path[..path.len() - 3].to_string();
+        }
+    }
+    keep_alive
+}
+
+/// Serve requests on a connection until the client closes it, asks for it to be closed, stays idle for too long or reaches `max_requests`
+async fn handle_stream(stream: TcpStream, server: Arc<Server>) {
+    let config = server.config;
+    let mut reader = RequestReader::new(stream, config.limits);
+    let mut served = 0;
+    loop {
+        match timeout(config.idle_timeout, reader.wait_for_request()).await {
+            Err(_) | Ok(Err(ReadError::Closed)) => return,
+            Ok(Err(e)) => {
+                eprintln!("Error reading request: {}", e);
+                return;
+            },
+            Ok(Ok(())) => {}
+        }
+        // A client sending its request too slowly would keep the connection open forever
+        let req = match timeout(config.request_timeout, reader.read_request()).await.unwrap_or(Err(ReadError::Timeout)) {
+            Ok(req) => req,
+            Err(ReadError::Closed) => return,
+            Err(ReadError::Io(e)) => {
+                eprintln!("Error reading request: {}", e);
+                return;
+            },
+            Err(e) => {
+                eprintln!("Error reading request: {}", e);
+                let status = match e {
+                    ReadError::HeadersTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                    ReadError::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+                    ReadError::Timeout => StatusCode::REQUEST_TIMEOUT,
+                    _ => StatusCode::BAD_REQUEST
+                };
+                // We don't know where the next request starts so the connection is closed
+                let res = http_bytes::Response::builder()
+                    .status(status)
+                    .header(http::header::CONNECTION, "close")
+                    .body(())
+                    .unwrap();
+                if let Err(e) = write_empty_response(res, reader.get_mut()).await {
+                    eprintln!("Error writing response: {}", e);
+                };
+                return;
+            }
+        };
+        served += 1;
+        let keep_alive = wants_keep_alive(&req) && served < config.max_requests;
+
+        if let Some(reloads) = &server.reloads {
+            if req.uri().path() == hot_reload::EVENTS_PATH {
+                if let Err(e) = hot_reload::send_events(reader.get_mut(), reloads.subscribe()).await {
+                    eprintln!("Error sending reload events: {}", e);
+                }
+                return;
             }
-            let content = read_to_string(entry.path()).unwrap();
-            env.add_template_owned(path.to_owned(), content).unwrap();
+        }
+
+        let mut res = match server.router.handle(req, server.assets.get(), server.db.clone(), server.dev_mode).await {
+            Ok(Some(res)) => res,
+            Ok(None) => http_bytes::Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Vec::new())
+                .unwrap(),
+            Err(HandleError::InternalServerError(e)) => {
+                eprintln!("Internal server error: {}", e);
+                http_bytes::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(b"Internal server error".to_vec())
+                    .unwrap()
+            },
+            Err(HandleError::BadRequest) => {
+                println!("Bad request");
+                http_bytes::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(b"Bad request".to_vec())
+                    .unwrap()
+            },
+            Err(HandleError::Unauthorized) => {
+                println!("Unauthorized");
+                http_bytes::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(b"Unauthorized".to_vec())
+                    .unwrap()
+            },
+            Err(HandleError::Forbidden) => {
+                println!("Forbidden");
+                http_bytes::Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body(b"Forbidden".to_vec())
+                    .unwrap()
+            },
+            Err(HandleError::NotFound) => {
+                println!("Not found");
+                http_bytes::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(b"Not found".to_vec())
+                    .unwrap()
+            }
+        };
+
+        let headers = res.headers_mut();
+        if keep_alive {
+            headers.insert(http::header::CONNECTION, http::HeaderValue::from_static("keep-alive"));
+            let params = format!("timeout={}, max={}", config.idle_timeout.as_secs(), config.max_requests - served);
+            headers.insert("keep-alive", http::HeaderValue::from_str(&params).unwrap());
+        } else {
+            headers.insert(http::header::CONNECTION, http::HeaderValue::from_static("close"));
+        }
+
+        if let Err(e) = write_response(res, reader.get_mut()).await {
+            eprintln!("Error writing response: {}", e);
+            return;
+        }
+        if !keep_alive {
+            return;
         }
     }
-    env
+}
+
+/// Get the value following `name` in the command line arguments (ex: `--max-body-size 4096`)
+fn arg_value<'a>(argv: &'a [String], name: &str) -> Option<&'a str> {
+    argv.iter()
+        .position(|a| a == name)
+        .and_then(|i| argv.get(i + 1))
+        .map(|v| v.as_str())
 }
 
 enum HandleError {
     InternalServerError(Error),
     BadRequest,
+    /// Nobody is logged in
+    Unauthorized,
+    /// The user doesn't have the permission
+    Forbidden,
     NotFound
 }
 impl From<Error> for HandleError {
@@ -161,137 +342,118 @@ impl From<Error> for HandleError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct HttpArgs(HashMap<String, String>);
 impl HttpArgs {
     pub fn new() -> Self {
         Self(HashMap::new())
     }
+    /// Parse `application/x-www-form-urlencoded` data (query strings and form bodies)
+    pub fn from_urlencoded(data: &str) -> Self {
+        let mut args = Self::new();
+        for arg in data.split('&') {
+            if let Some((name, value)) = arg.split_once('=') {
+                let name = url_escape::decode(&name.replace('+', " ")).into_owned();
+                let value = url_escape::decode(&value.replace('+', " ")).into_owned();
+                args.insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+        args
+    }
     pub fn insert(&mut self, k: String, v: String) {
         self.0.insert(k, v);
     }
 }
 impl StructObject for HttpArgs {
     fn get_field(&self, name: &str) -> Option<minijinja::Value> {
-        match self.0.get(name) {
-            None => None,
-            Some(v) => Some(minijinja::Value::from(v.clone()))
-        }
+        self.0.get(name).map(|v| minijinja::Value::from(v.clone()))
     }
 }
 
-async fn handle_connection<'a>(req: &http::Request<Option<String>>, env: Arc<Environment<'_>>, db: Arc<DB>, dev_mode: bool) -> Result<Option<http::Response<Vec<u8>>>, HandleError> {
-    let mut path = req.uri().path();
-    println!("Got request for: {}", path);
-    if path == "/" {
-        path = "/index.html";
+/// Context given to the page templates: the query arguments, `user` for the logged in user and `can` for their permissions
+#[derive(Debug)]
+struct TemplateContext {
+    args: HttpArgs,
+    user: Option<User>,
+    permissions: Permissions
+}
+impl StructObject for TemplateContext {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "user" => self.user.clone().map(minijinja::Value::from_struct_object),
+            "can" => Some(minijinja::Value::from_struct_object(self.permissions.clone())),
+            _ => self.args.get_field(name)
+        }
     }
-
-    let mut args = HttpArgs::new();
-    for arg in req.uri().query().unwrap_or("").split('&') {
-        if let Some((name, value)) = arg.split_once('=') {
-            args.insert(name.into(), url_escape::decode(value).into_owned());
+}
+impl StructObject for User {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "id" => Some(self.id.into()),
+            "name" => Some(self.name.clone().into()),
+            "role" => Some(self.role.as_str().into()),
+            "created_at" => Some(self.created_at.to_string().into()),
+            _ => None
         }
     }
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["id", "name", "role", "created_at"])
+    }
+}
 
-    if path.starts_with("/api/") {
-        return handle_api(req, path, args, db).await;
-    }
-
-    let template = match env.get_template(path) {
-        Ok(t) => t,
-        Err(e) => {
-            if let minijinja::ErrorKind::TemplateNotFound = e.kind() {
-                // Try various extensions
-                match env.get_template(&(path.to_string() + ".html")) {
-                    Ok(t) => t,
-                    Err(e) => {
-                        // TODO: Check if it's a directory and serve index.html
-                        if let minijinja::ErrorKind::TemplateNotFound = e.kind() {
-                            return Err(HandleError::NotFound);
-                        } else {
-                            return Err(HandleError::InternalServerError(e.into()));
-                        }
-                    }
-                }
-            } else {
-                return Err(HandleError::InternalServerError(e.into()));
-            }
+impl StructObject for Homework {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "id" => Some(self.id.into()),
+            "name" => Some(self.name.clone().into()),
+            "description" => Some(self.description.clone().into()),
+            "class_id" => Some(self.class_id.into()),
+            "lesson_id" => self.lesson_id.map(|id| id.into()),
+            "due_date" => Some(self.due_date.to_string().into()),
+            _ => None
         }
-    };
-
-    let res = match template.render(minijinja::Value::from_struct_object(args)) {
-        Ok(b) => b,
-        Err(e) => return Err(HandleError::InternalServerError(e.into()))
-    };
-    let res = res.as_bytes();
-
-    let res = http::Response::builder()
-        .body(res.to_owned())
-        .unwrap();
-
-    Ok(Some(res))
+    }
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["id", "name", "description", "class_id", "lesson_id", "due_date"])
+    }
 }
-
-fn get_put_args(body: &str) -> HttpArgs {
-    let mut args = HttpArgs::new();
-    for line in body.split('&') {
-        let (name, value) = match line.split_once('=') {
-            None => continue,
-            Some((n, v)) => (n, v)
-        };
-        args.insert(name.trim().to_string(), value.trim().to_string());
+impl StructObject for TrashItem {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "kind" => Some(self.kind.table().into()),
+            "label" => Some(self.kind.label().into()),
+            "id" => Some(self.id.into()),
+            "name" => Some(self.name.clone().into()),
+            "deleted_at" => Some(self.deleted_at.format("%d/%m/%Y %H:%M").to_string().into()),
+            _ => None
+        }
+    }
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["kind", "label", "id", "name", "deleted_at"])
     }
-    args
 }
 
-async fn handle_api(req: &http::Request<Option<String>>, path: &str, args: HttpArgs, db: Arc<DB>) -> Result<Option<http::Response<Vec<u8>>>, HandleError> {
-    // split the "/api/"
-    let path = path[5..].to_string();
-    match path.as_str() {
-        "create_class" => {
-            if req.method() != http::Method::PUT {
-                return Err(HandleError::BadRequest);
-            }
-
-            let args = get_put_args(req.body().as_deref().unwrap_or("").trim_end_matches('\0'));
-            
-            let name = match args.0.get("name") {
-                None => return Err(HandleError::BadRequest),
-                Some(n) => n.clone()
-            };
-
-            db.insert_class(name).await?;
-        },
-        "create_user" => {
-            if req.method() != http::Method::PUT {
-                return Err(HandleError::BadRequest);
-            }
-
-            let args = get_put_args(req.body().as_deref().unwrap_or("").trim_end_matches('\0'));
-
-            let name = match args.0.get("name") {
-                None => return Err(HandleError::BadRequest),
-                Some(n) => n.clone()
-            };
-            let password = match args.0.get("password") {
-                None => return Err(HandleError::BadRequest),
-                Some(n) => n.clone()
-            };
-
-            dbg!(0);
-            let password_hash = argon2::hash_encoded(&password.into_bytes(), &md5::compute(&name.clone().into_bytes()).0, &argon2::Config::default()).unwrap();
-            println!("Creating user: {}", name);
+impl StructObject for Conflict {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "kind" => Some(self.kind.as_str().into()),
+            "label" => Some(self.kind.label().into()),
+            "name" => Some(self.name.clone().into()),
+            "lesson_id" => Some(self.lesson_id.into()),
+            "lesson_name" => Some(self.lesson_name.clone().into()),
+            "start" => Some(dates::format_datetime(self.start).into()),
+            "end" => Some(self.end.format("%H:%M").to_string().into()),
+            _ => None
         }
-        _ => return Err(HandleError::NotFound)
     }
-
-    Ok(None)
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["kind", "label", "name", "lesson_id", "lesson_name", "start", "end"])
+    }
 }
 
 /// Code from this : https://docs.rs/simple-server/latest/src/simple_server/lib.rs.html#1-495
 /// but modified for tokio
-async fn write_response<'a, S: AsyncWrite + Unpin>(
+async fn write_response<S: AsyncWrite + Unpin>(
     response: http::Response<Vec<u8>>,
     mut stream: S,
 ) -> std::io::Result<()> {
@@ -317,7 +479,9 @@ async fn write_response<'a, S: AsyncWrite + Unpin>(
     if !parts.headers.contains_key(http::header::CONNECTION) {
         write!(text, "connection: close\r\n").unwrap();
     }
-    if !parts.headers.contains_key(http::header::CONTENT_LENGTH) {
+    // 204 and 304 responses can't have a body
+    let no_body = parts.status == StatusCode::NO_CONTENT || parts.status == StatusCode::NOT_MODIFIED;
+    if !no_body && !parts.headers.contains_key(http::header::CONTENT_LENGTH) {
         write!(text, "content-length: {}\r\n", body.len()).unwrap();
     }
     for (k, v) in parts.headers.iter() {
@@ -326,8 +490,8 @@ async fn write_response<'a, S: AsyncWrite + Unpin>(
 
     write!(text, "\r\n").unwrap();
 
-    stream.write(text.as_bytes()).await?;
-    stream.write(body).await?;
+    stream.write_all(text.as_bytes()).await?;
+    stream.write_all(body).await?;
     stream.flush().await?;
     Ok(())
 }
@@ -363,7 +527,29 @@ async fn write_empty_response<S: AsyncWrite + Unpin>(
 
     write!(text, "\r\n").unwrap();
 
-    stream.write(text.as_bytes()).await?;
+    stream.write_all(text.as_bytes()).await?;
     stream.flush().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_alive() {
+        let request = |version, connection: Option<&str>| {
+            let mut builder = http::Request::builder();
+            builder.version(version);
+            if let Some(connection) = connection {
+                builder.header(http::header::CONNECTION, connection);
+            }
+            builder.body(()).unwrap()
+        };
+        assert!(wants_keep_alive(&request(http::Version::HTTP_11, None)));
+        assert!(!wants_keep_alive(&request(http::Version::HTTP_11, Some("Close"))));
+        assert!(!wants_keep_alive(&request(http::Version::HTTP_11, Some("upgrade, close"))));
+        assert!(!wants_keep_alive(&request(http::Version::HTTP_10, None)));
+        assert!(wants_keep_alive(&request(http::Version::HTTP_10, Some("Keep-Alive"))));
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{Result, anyhow};
+use tokio_rusqlite::Connection;
+
+/// Schema migrations in the order they are applied.
+///
+/// The version of a database (`PRAGMA user_version`) is the number of migrations applied to it, so new migrations must be added at the end and existing ones never changed.
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("initial", include_str!("../migrations/0001_initial.sql")),
+    ("sessions", include_str!("../migrations/0002_sessions.sql")),
+    ("roles", include_str!("../migrations/0003_roles.sql")),
+    ("soft delete links", include_str!("../migrations/0004_soft_delete_links.sql")),
+    ("homework due dates", include_str!("../migrations/0005_homework_due_dates.sql")),
+    ("recurring lessons", include_str!("../migrations/0006_recurring_lessons.sql")),
+    ("lesson conflicts", include_str!("../migrations/0007_lesson_conflicts.sql")),
+    ("teachers", include_str!("../migrations/0008_teachers.sql")),
+    ("class join codes", include_str!("../migrations/0009_class_join_codes.sql")),
+    ("unique usernames", include_str!("../migrations/0010_unique_usernames.sql"))
+];
+
+/// Version of the schema expected by this binary
+pub const LATEST_VERSION: usize = MIGRATIONS.len();
+
+/// Apply the migrations the database doesn't have yet, each one in its own transaction
+pub async fn migrate(conn: &Connection) -> Result<()> {
+    let version = conn.call(|conn| {
+        conn.query_row("PRAGMA user_version", (), |row| row.get::<_, usize>(0))
+    }).await?;
+    if version > LATEST_VERSION {
+        return Err(anyhow!(
+            "The database schema (version {}) is newer than this version of the server (version {})",
+            version, LATEST_VERSION
+        ));
+    }
+
+    for (i, (name, sql)) in MIGRATIONS.iter().enumerate().skip(version) {
+        let new_version = i + 1;
+        println!("Applying migration {} ({})", new_version, name);
+        conn.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", new_version)?;
+            tx.commit()
+        }).await.map_err(|e| anyhow!("Migration {} ({}) failed : {}", new_version, name, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn version(conn: &Connection) -> usize {
+        conn.call(|conn| conn.query_row("PRAGMA user_version", (), |row| row.get(0))).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn fresh_database() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        migrate(&conn).await.unwrap();
+        assert_eq!(version(&conn).await, LATEST_VERSION);
+        assert_eq!(LATEST_VERSION, 10);
+
+        // Running them again doesn't change anything
+        conn.call(|conn| conn.execute("INSERT INTO classes (name, join_code, created_at) VALUES ('1A', 'ABCD', datetime('now'))", ())).await.unwrap();
+        migrate(&conn).await.unwrap();
+        assert_eq!(version(&conn).await, LATEST_VERSION);
+        let classes: usize = conn.call(|conn| conn.query_row("SELECT COUNT(*) FROM classes", (), |row| row.get(0))).await.unwrap();
+        assert_eq!(classes, 1);
+    }
+
+    #[tokio::test]
+    async fn rooms_are_ids() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        migrate(&conn).await.unwrap();
+        let columns = |table: &'static str| conn.call(move |conn| {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?)")?;
+            let columns = stmt.query_map([table], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(columns)
+        });
+        assert!(!columns("lessons").await.unwrap().contains(&"room".to_string()));
+        let exception_columns = columns("lesson_exceptions").await.unwrap();
+        assert!(exception_columns.contains(&"room_id".to_string()) && !exception_columns.contains(&"room".to_string()));
+        assert_eq!(columns("lesson_rooms").await.unwrap(), ["lesson_id", "room_id", "deleted_at"]);
+    }
+
+    #[tokio::test]
+    async fn newer_database() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        conn.call(|conn| conn.pragma_update(None, "user_version", LATEST_VERSION + 1)).await.unwrap();
+        let error = migrate(&conn).await.unwrap_err();
+        assert!(error.to_string().contains("is newer than this version of the server"));
+        assert_eq!(version(&conn).await, LATEST_VERSION + 1);
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::OnceLock;
+use anyhow::Result;
+use base64::Engine;
+
+/// Argon2id parameters used for new hashes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashParams {
+    /// Memory in KiB
+    pub mem_cost: u32,
+    pub time_cost: u32,
+    pub lanes: u32
+}
+impl Default for HashParams {
+    fn default() -> Self {
+        Self {
+            mem_cost: 64 * 1024,
+            time_cost: 3,
+            lanes: 1
+        }
+    }
+}
+impl HashParams {
+    fn config(&self) -> argon2::Config<'static> {
+        argon2::Config {
+            variant: argon2::Variant::Argon2id,
+            version: argon2::Version::Version13,
+            mem_cost: self.mem_cost,
+            time_cost: self.time_cost,
+            lanes: self.lanes,
+            ..argon2::Config::rfc9106_low_mem()
+        }
+    }
+}
+
+static PARAMS: OnceLock<HashParams> = OnceLock::new();
+
+/// Set the parameters used for new hashes by the server. Must be called before hashing anything
+pub fn set_params(params: HashParams) {
+    PARAMS.set(params).expect("Hash parameters already set");
+}
+/// Parameters set with `set_params`, or the default ones
+pub fn params() -> HashParams {
+    *PARAMS.get_or_init(HashParams::default)
+}
+
+/// Hash a password with a random salt. The salt and parameters are stored in the encoded hash
+pub async fn hash(password: String, params: HashParams) -> Result<String> {
+    let salt = rand::random::<[u8; 16]>();
+    let hash = tokio::task::spawn_blocking(move || {
+        argon2::hash_encoded(password.as_bytes(), &salt, &params.config())
+    }).await??;
+    Ok(hash)
+}
+
+pub async fn verify(hash: String, password: String) -> Result<bool> {
+    let ok = tokio::task::spawn_blocking(move || {
+        argon2::verify_encoded(&hash, password.as_bytes())
+    }).await??;
+    Ok(ok)
+}
+
+/// Check if `hash` was made with other parameters than `params`, or with the old salt (md5 of the username)
+pub fn needs_rehash(hash: &str, username: &str, params: HashParams) -> bool {
+    // $argon2id$v=19$m=65536,t=3,p=1$<salt>$<hash>
+    let parts = hash.split('$').collect::<Vec<_>>();
+    if parts.len() != 6 || parts[1] != "argon2id" || parts[2] != "v=19" {
+        return true;
+    }
+    if parts[3] != format!("m={},t={},p={}", params.mem_cost, params.time_cost, params.lanes) {
+        return true;
+    }
+    match base64::engine::general_purpose::STANDARD_NO_PAD.decode(parts[4]) {
+        Err(_) => true,
+        Ok(salt) => salt == md5::compute(username.as_bytes()).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hashes() {
+        // Cheap parameters so the tests stay fast
+        let cheap = HashParams { mem_cost: 1024, time_cost: 1, lanes: 1 };
+
+        let hash = hash("correct horse".to_string(), cheap).await.unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(verify(hash.clone(), "correct horse".to_string()).await.unwrap());
+        assert!(!verify(hash.clone(), "Correct horse".to_string()).await.unwrap());
+        // Each hash has its own salt
+        assert_ne!(hash, super::hash("correct horse".to_string(), cheap).await.unwrap());
+        assert!(!needs_rehash(&hash, "alice", cheap));
+
+        // Other parameters, or the old salt derived from the username
+        let old_params = argon2::hash_encoded(b"pw", &[0; 16], &HashParams { time_cost: 2, ..cheap }.config()).unwrap();
+        assert!(needs_rehash(&old_params, "alice", cheap));
+        let old_salt = argon2::hash_encoded(b"pw", &md5::compute("alice").0, &cheap.config()).unwrap();
+        assert!(needs_rehash(&old_salt, "alice", cheap));
+        assert!(!needs_rehash(&old_salt, "bob", cheap));
+        assert!(needs_rehash("$argon2i$v=19$m=1024,t=1,p=1$c2FsdHNhbHQ$aGFzaA", "alice", cheap));
+        assert!(needs_rehash("not a hash", "alice", cheap));
+    }
+}
@@ -0,0 +1,205 @@
+use minijinja::value::StructObject;
+use anyhow::Result;
+use crate::{db::{DB, Membership, Role, User}, HandleError};
+
+/// Something a user may be allowed to do
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Permission {
+    /// See the timetable and the homeworks of their classes
+    ViewClasses,
+    CreateClass,
+    /// Join a class with its join code
+    JoinClass,
+    /// Create, edit or delete the homeworks of a class
+    ManageHomeworks(usize),
+    /// See the members of a class and its join code, remove them and change their roles
+    ManageMembers(usize),
+    /// Create or edit the lessons of a class
+    ManageLessons(usize),
+    /// Create, rename or delete the rooms and teachers
+    ManageResources,
+    /// See the deleted items and restore them
+    ManageTrash
+}
+
+/// Roles of the current user, used to check what they can do
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+    /// `None` when nobody is logged in
+    role: Option<Role>,
+    memberships: Vec<Membership>
+}
+impl Permissions {
+    pub async fn load(db: &DB, user: Option<&User>) -> Result<Self> {
+        match user {
+            None => Ok(Self::default()),
+            Some(user) => Ok(Self {
+                role: Some(user.role),
+                memberships: db.get_memberships(user.id).await?
+            })
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Some(Role::Admin)
+    }
+
+    /// Classes the user is a member of
+    pub fn classes(&self) -> Vec<usize> {
+        self.memberships.iter().map(|m| m.class_id).collect()
+    }
+
+    /// Role of the user in the class, if they are a member
+    pub fn class_role(&self, class_id: usize) -> Option<Role> {
+        self.memberships.iter()
+            .find(|m| m.class_id == class_id)
+            .map(|m| m.role)
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        match self.role {
+            None => return false,
+            Some(Role::Admin) => return true,
+            Some(_) => {}
+        }
+        match permission {
+            Permission::ViewClasses | Permission::JoinClass => true,
+            Permission::CreateClass | Permission::ManageResources | Permission::ManageTrash => false,
+            Permission::ManageHomeworks(class_id) | Permission::ManageMembers(class_id) => {
+                matches!(self.class_role(class_id), Some(Role::Teacher | Role::Delegate))
+            }
+            Permission::ManageLessons(class_id) => self.class_role(class_id) == Some(Role::Teacher)
+        }
+    }
+
+    /// Fail with 401 if nobody is logged in, or 403 if the user doesn't have the permission
+    pub fn check(&self, permission: Permission) -> Result<(), HandleError> {
+        if self.role.is_none() {
+            return Err(HandleError::Unauthorized);
+        }
+        if !self.can(permission) {
+            return Err(HandleError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Whether the user has a permission of a class in at least one of their classes, ex: `can_any(Permission::ManageLessons)`
+    pub fn can_any(&self, permission: fn(usize) -> Permission) -> bool {
+        self.is_admin() || self.memberships.iter().any(|m| self.can(permission(m.class_id)))
+    }
+
+    /// Same as `check`, for a permission of a class in at least one of their classes
+    pub fn check_any(&self, permission: fn(usize) -> Permission) -> Result<(), HandleError> {
+        if self.role.is_none() {
+            return Err(HandleError::Unauthorized);
+        }
+        if !self.can_any(permission) {
+            return Err(HandleError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Whether the user can remove a member having `role` from the class, or give `role` to a member.
+    ///
+    /// Delegates can only manage the students and the delegates
+    pub fn can_manage_member(&self, class_id: usize, role: Role) -> bool {
+        self.can(Permission::ManageMembers(class_id))
+            && (role != Role::Teacher || self.is_admin() || self.class_role(class_id) == Some(Role::Teacher))
+    }
+}
+
+/// Lets the templates hide the controls the user can't use (`can.create_class`, ...)
+impl StructObject for Permissions {
+    fn get_field(&self, name: &str) -> Option<minijinja::Value> {
+        match name {
+            "create_class" => Some(self.can(Permission::CreateClass).into()),
+            "join_class" => Some(self.can(Permission::JoinClass).into()),
+            "manage_homeworks" => Some(self.can_any(Permission::ManageHomeworks).into()),
+            "manage_members" => Some(self.can_any(Permission::ManageMembers).into()),
+            "manage_lessons" => Some(self.can_any(Permission::ManageLessons).into()),
+            "manage_resources" => Some(self.can(Permission::ManageResources).into()),
+            "manage_trash" => Some(self.can(Permission::ManageTrash).into()),
+            "is_admin" => Some(self.is_admin().into()),
+            _ => None
+        }
+    }
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["create_class", "join_class", "manage_homeworks", "manage_members", "manage_lessons", "manage_resources", "manage_trash", "is_admin"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// User with the site role `role`, member of the classes 1 and 2 with the given roles
+    fn permissions(role: Role, first: Role, second: Option<Role>) -> Permissions {
+        let membership = |class_id, role| Membership { user_id: 1, class_id, role };
+        Permissions {
+            role: Some(role),
+            memberships: std::iter::once(membership(1, first)).chain(second.map(|r| membership(2, r))).collect()
+        }
+    }
+
+    #[test]
+    fn matrix() {
+        let anonymous = Permissions::default();
+        let student = permissions(Role::Student, Role::Student, None);
+        let delegate = permissions(Role::Student, Role::Delegate, None);
+        let teacher = permissions(Role::Teacher, Role::Teacher, None);
+        let admin = Permissions { role: Some(Role::Admin), memberships: Vec::new() };
+
+        // (permission, anonymous, student, delegate, teacher, admin)
+        let expected = [
+            (Permission::ViewClasses, false, true, true, true, true),
+            (Permission::CreateClass, false, false, false, false, true),
+            (Permission::JoinClass, false, true, true, true, true),
+            (Permission::ManageHomeworks(1), false, false, true, true, true),
+            (Permission::ManageMembers(1), false, false, true, true, true),
+            (Permission::ManageLessons(1), false, false, false, true, true),
+            (Permission::ManageResources, false, false, false, false, true),
+            (Permission::ManageTrash, false, false, false, false, true),
+            // Not a member of the class 2
+            (Permission::ManageHomeworks(2), false, false, false, false, true),
+            (Permission::ManageLessons(2), false, false, false, false, true)
+        ];
+        for (permission, a, s, d, t, ad) in expected {
+            assert_eq!(
+                (anonymous.can(permission), student.can(permission), delegate.can(permission), teacher.can(permission), admin.can(permission)),
+                (a, s, d, t, ad),
+                "{:?}", permission
+            );
+        }
+
+        assert!(matches!(anonymous.check(Permission::JoinClass), Err(HandleError::Unauthorized)));
+        assert!(matches!(student.check(Permission::ManageHomeworks(1)), Err(HandleError::Forbidden)));
+        assert!(delegate.check(Permission::ManageHomeworks(1)).is_ok());
+
+        // In at least one class
+        assert!(!student.can_any(Permission::ManageHomeworks) && delegate.can_any(Permission::ManageHomeworks));
+        assert!(!delegate.can_any(Permission::ManageLessons) && teacher.can_any(Permission::ManageLessons));
+        assert!(admin.can_any(Permission::ManageMembers));
+        assert!(matches!(anonymous.check_any(Permission::ManageLessons), Err(HandleError::Unauthorized)));
+        assert!(matches!(delegate.check_any(Permission::ManageLessons), Err(HandleError::Forbidden)));
+        assert!(teacher.check_any(Permission::ManageLessons).is_ok());
+    }
+
+    #[test]
+    fn members_and_homeworks() {
+        let delegate = permissions(Role::Student, Role::Delegate, Some(Role::Student));
+        assert!(delegate.can_manage_member(1, Role::Student));
+        assert!(delegate.can_manage_member(1, Role::Delegate));
+        assert!(!delegate.can_manage_member(1, Role::Teacher));
+        assert!(!delegate.can_manage_member(2, Role::Student));
+        assert!(delegate.can_any(Permission::ManageHomeworks));
+
+        let teacher = permissions(Role::Teacher, Role::Teacher, Some(Role::Delegate));
+        assert!(teacher.can_manage_member(1, Role::Teacher));
+        assert!(!teacher.can_manage_member(2, Role::Teacher));
+        assert!(teacher.can_any(Permission::ManageHomeworks));
+
+        let admin = Permissions { role: Some(Role::Admin), memberships: Vec::new() };
+        assert!(admin.can_manage_member(3, Role::Teacher));
+        assert!(!Permissions::default().can_any(Permission::ManageHomeworks));
+    }
+}
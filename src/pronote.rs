@@ -0,0 +1,6 @@
+/// Protocol of the Pronote web app, as reverse engineered in `reverse engineering/`
+pub mod crypto;
+pub mod instance;
+pub mod login;
+pub mod messages;
+pub mod types;
@@ -0,0 +1,223 @@
+use std::fmt::Display;
+use aes::cipher::{KeyIvInit, BlockDecryptMut, BlockEncryptMut, block_padding::Pkcs7};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use openssl::{bn::BigNum, rsa::{Padding, Rsa}};
+
+type AesCbcEncryptor = cbc::Encryptor<aes::Aes128>;
+type AesCbcDecryptor = cbc::Decryptor<aes::Aes128>;
+
+/// Public RSA key of the server, in hexadecimal
+#[derive(Clone, Copy, Debug)]
+pub struct RsaKey<'a> {
+    pub modulus: &'a str,
+    pub exponent: &'a str
+}
+
+/// Key used by `encrypter1024` in the script of the instance (`objetcryptagersa.js`)
+pub const RSA_1024: RsaKey<'static> = RsaKey {
+    modulus: "B99B77A3D72D3A29B4271FC7B7300E2F791EB8948174BE7B8024667E915446D4EEA0C2424B8D1EBF7E2DDFF94691C6E994E839225C627D140A8F1146D1B0B5F18A09BBD3D8F421CA1E3E4796B301EEBCCF80D81A32A1580121B8294433C38377083C5517D5921E8A078CDC019B15775292EFDA2C30251B1CCABE812386C893E5",
+    exponent: "010001"
+};
+
+/// Invalid padding after decrypting a message, it was encrypted with another key
+#[derive(Debug)]
+pub struct WrongKey;
+impl Display for WrongKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid padding, the message was encrypted with another key")
+    }
+}
+impl std::error::Error for WrongKey {}
+
+/// Keys used to encrypt the messages of a session, like `CommunicationProduit` in the web client.
+///
+/// The messages are encrypted with AES-128-CBC, the key being the MD5 of the key bytes (empty until the authentication).
+/// The IV is zero for the `FonctionParametres` request, then the MD5 of `iv_temp`, which the client sends in that request
+#[derive(Clone)]
+pub struct Crypto {
+    key: [u8; 16],
+    iv: [u8; 16],
+    iv_temp: [u8; 16]
+}
+impl Crypto {
+    pub fn new() -> Self {
+        Self::with_iv(rand::random())
+    }
+    pub fn with_iv(iv_temp: [u8; 16]) -> Self {
+        Self {
+            key: md5::compute([]).0,
+            iv: [0; 16],
+            iv_temp
+        }
+    }
+
+    /// `Uuid` of the `FonctionParametres` request (`serialiserIVAESPourSeveur`): the temporary IV in base64, RSA encrypted first if the instance is served over HTTP.
+    ///
+    /// Like the web client, the base64 is split in lines of 64 characters
+    pub fn serialize_iv(&self, rsa: Option<RsaKey>) -> Result<String> {
+        let iv = match rsa {
+            None => self.iv_temp.to_vec(),
+            Some(rsa) => {
+                let key = Rsa::from_public_components(BigNum::from_hex_str(rsa.modulus)?, BigNum::from_hex_str(rsa.exponent)?)?;
+                let mut encrypted = vec![0; key.size() as usize];
+                let len = key.public_encrypt(&self.iv_temp, &mut encrypted, Padding::PKCS1)?;
+                encrypted.truncate(len);
+                encrypted
+            }
+        };
+        let base64 = base64::engine::general_purpose::STANDARD.encode(iv);
+        Ok(base64.as_bytes()
+            .chunks(64)
+            .map(|line| String::from_utf8_lossy(line))
+            .collect::<Vec<_>>()
+            .join("\r\n"))
+    }
+    /// Use the IV sent in `FonctionParametres`, for every message after its request
+    pub fn switch_iv(&mut self) {
+        self.iv = md5::compute(self.iv_temp).0;
+    }
+    /// Use the key given by the server after the authentication
+    pub fn set_key(&mut self, key: &[u8]) {
+        self.key = md5::compute(key).0;
+    }
+    /// Same IV with another key, to solve the authentication challenge
+    fn with_key(&self, key: &[u8]) -> Self {
+        let mut crypto = self.clone();
+        crypto.set_key(key);
+        crypto
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        // Room for the padding, which is always added
+        let mut buf = data.to_vec();
+        buf.resize(data.len() + 16 - data.len() % 16, 0);
+        let len = AesCbcEncryptor::new(&self.key.into(), &self.iv.into())
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, data.len())
+            .expect("The buffer has room for the padding")
+            .len();
+        buf.truncate(len);
+        buf
+    }
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = data.to_vec();
+        let len = AesCbcDecryptor::new(&self.key.into(), &self.iv.into())
+            .decrypt_padded_mut::<Pkcs7>(&mut buf)
+            .map_err(|_| WrongKey)?
+            .len();
+        buf.truncate(len);
+        Ok(buf)
+    }
+    /// `numeroOrdre` of a message, the encrypted counter in hexadecimal
+    pub fn numero_ordre(&self, counter: u32) -> String {
+        hex::encode(self.encrypt(counter.to_string().as_bytes()))
+    }
+
+    /// Answer to the `challenge` of the `Identification` response, sent in `Authentification`.
+    ///
+    /// `user_key` is the identifiant followed by the hashed password. The challenge is decrypted with it, every other character is removed and the rest is encrypted again.
+    ///
+    /// Fails with `WrongKey` if the identifiant or the password is wrong
+    pub fn solve_challenge(&self, user_key: &str, challenge: &str) -> Result<String> {
+        let crypto = self.with_key(user_key.as_bytes());
+        let decrypted = crypto.decrypt(&hex::decode(challenge)?)
+            .context("Can't decrypt the challenge, the identifiant or the password is wrong")?;
+        let solved = String::from_utf8(decrypted)?
+            .chars()
+            .step_by(2)
+            .collect::<String>();
+        Ok(hex::encode(crypto.encrypt(solved.as_bytes())))
+    }
+    /// Key of the session, from the `cle` of the `Authentification` response: its bytes separated by commas, encrypted with `user_key`
+    pub fn session_key(&self, user_key: &str, cle: &str) -> Result<Vec<u8>> {
+        let decrypted = self.with_key(user_key.as_bytes()).decrypt(&hex::decode(cle)?)?;
+        String::from_utf8(decrypted)?
+            .split(',')
+            .map(|byte| byte.trim().parse().map_err(|_| anyhow!("Invalid byte in the session key: {:?}", byte)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exchanges of the demo account captured from the web client
+    const CAPTURE: &str = include_str!("../../reverse engineering/requetes");
+    /// Password of the public demo account, the capture only contains its hash
+    const DEMO_PASSWORD: &str = "pronotevs";
+
+    /// Values of `field` in the capture, in order
+    fn captured(field: &str) -> Vec<&'static str> {
+        let prefix = format!("\"{}\": \"", field);
+        CAPTURE.lines()
+            .filter_map(|line| line.trim().strip_prefix(prefix.as_str())?.split('"').next())
+            .collect()
+    }
+
+    /// Client of the capture, with the IV it sent
+    fn captured_client() -> Crypto {
+        let uuid = base64::engine::general_purpose::STANDARD.decode(captured("Uuid")[0]).unwrap();
+        Crypto::with_iv(uuid.try_into().unwrap())
+    }
+
+    fn user_key() -> String {
+        let identification = serde_json::from_value(serde_json::json!({
+            "alea": captured("alea")[0],
+            "modeCompLog": 1,
+            "modeCompMdp": 0,
+            "challenge": captured("challenge")[0]
+        })).unwrap();
+        crate::pronote::login::user_key("Demonstration", DEMO_PASSWORD, &identification)
+    }
+
+    #[test]
+    fn handshake() {
+        let mut crypto = captured_client();
+        assert_eq!(crypto.serialize_iv(None).unwrap(), captured("Uuid")[0]);
+        let numeros = captured("numeroOrdre");
+        // FonctionParametres with a zero IV, then the IV sent in it for its response, Identification and Authentification
+        assert_eq!(crypto.numero_ordre(1), numeros[0]);
+        crypto.switch_iv();
+        for counter in 2..=6 {
+            assert_eq!(crypto.numero_ordre(counter), numeros[counter as usize - 1].to_lowercase());
+        }
+
+        let user_key = user_key();
+        assert_eq!(crypto.solve_challenge(&user_key, captured("challenge")[0]).unwrap(), captured("challenge")[1]);
+        assert!(crypto.solve_challenge("demonstrationWRONG", captured("challenge")[0]).unwrap_err().is::<WrongKey>());
+        assert!(!crypto.solve_challenge(&user_key, "not hex").unwrap_err().is::<WrongKey>());
+        let key = crypto.session_key(&user_key, captured("cle")[0]).unwrap();
+        assert_eq!(key.len(), 32);
+        // ParametresUtilisateur and Navigation use the key of the session
+        crypto.set_key(&key);
+        for counter in 7..=9 {
+            assert_eq!(crypto.numero_ordre(counter), numeros[counter as usize - 1].to_lowercase());
+        }
+    }
+
+    #[test]
+    fn encryption() {
+        let mut crypto = Crypto::with_iv([1; 16]);
+        crypto.switch_iv();
+        crypto.set_key(b"key");
+        let encrypted = crypto.encrypt(b"donnees");
+        assert_eq!(encrypted.len(), 16);
+        assert_eq!(crypto.decrypt(&encrypted).unwrap(), b"donnees");
+        assert!(Crypto::new().decrypt(&encrypted).unwrap_err().is::<WrongKey>());
+    }
+
+    #[test]
+    fn rsa_iv() {
+        let private = Rsa::generate(1024).unwrap();
+        let (modulus, exponent) = (private.n().to_hex_str().unwrap(), private.e().to_hex_str().unwrap());
+        let crypto = Crypto::new();
+        let uuid = crypto.serialize_iv(Some(RsaKey { modulus: &modulus, exponent: &exponent })).unwrap();
+        assert_eq!(uuid.lines().map(|l| l.trim_end().len()).collect::<Vec<_>>(), [64, 64, 44]);
+        let uuid = base64::engine::general_purpose::STANDARD.decode(uuid.replace("\r\n", "")).unwrap();
+        let mut iv = vec![0; 128];
+        let len = private.private_decrypt(&uuid, &mut iv, Padding::PKCS1).unwrap();
+        assert_eq!(&iv[..len], crypto.iv_temp);
+        assert_eq!(crypto.serialize_iv(Some(RSA_1024)).unwrap().len(), 172 + 2 * 2);
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{Result, anyhow, Context};
+use reqwest::Url;
+
+/// Public demo of Pronote, its accounts are given on the page
+pub const DEMO_URL: &str = "https://demo.index-education.net/pronote/eleve.html";
+
+/// Kind of user of an instance, each one has its own page
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Espace {
+    Eleve,
+    Parent,
+    Professeur,
+    VieScolaire,
+    Accompagnant,
+    Entreprise,
+    Academie,
+    Tuteur
+}
+impl Espace {
+    const ALL: [Self; 8] = [Self::Eleve, Self::Parent, Self::Professeur, Self::VieScolaire, Self::Accompagnant, Self::Entreprise, Self::Academie, Self::Tuteur];
+
+    /// `genreEspace` (`EGenreEspace` in the web client), also in the path of the requests
+    pub fn genre(&self) -> u8 {
+        match self {
+            Self::Professeur => 1,
+            Self::Parent => 2,
+            Self::Eleve => 3,
+            Self::Entreprise => 4,
+            Self::Academie => 5,
+            Self::VieScolaire => 13,
+            Self::Accompagnant => 25,
+            Self::Tuteur => 29
+        }
+    }
+    /// Page of the web client
+    pub fn page(&self) -> &'static str {
+        match self {
+            Self::Eleve => "eleve.html",
+            Self::Parent => "parent.html",
+            Self::Professeur => "professeur.html",
+            Self::VieScolaire => "viescolaire.html",
+            Self::Accompagnant => "accompagnant.html",
+            Self::Entreprise => "entreprise.html",
+            Self::Academie => "academie.html",
+            Self::Tuteur => "tuteur.html"
+        }
+    }
+    /// Espace of a page, the mobile pages (ex: `mobile.eleve.html`) being the same espaces
+    pub fn from_page(page: &str) -> Option<Self> {
+        let page = page.to_lowercase();
+        let page = page.strip_prefix("mobile.").unwrap_or(&page);
+        Self::ALL.into_iter().find(|espace| espace.page() == page)
+    }
+}
+
+/// Espace of a Pronote instance
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    /// Directory of the pages, ex: `https://demo.index-education.net/pronote/`
+    pub base_url: Url,
+    pub espace: Espace
+}
+impl Instance {
+    /// Instance of a URL pasted by a user, ex: `demo.index-education.net/pronote/mobile.eleve.html?login=true`
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = url.trim();
+        let url = match url.contains("://") {
+            false => format!("https://{}", url),
+            true => url.to_string()
+        };
+        let mut url = Url::parse(&url).with_context(|| format!("Invalid URL : {}", url))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(anyhow!("Not a web address : {}", url));
+        }
+        url.set_query(None);
+        url.set_fragment(None);
+
+        let page = url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+            .to_string();
+        let espace = match Espace::from_page(&page) {
+            None => return Err(anyhow!(
+                "No espace in the URL {}, it should end with the page of the espace, ex: {}",
+                url, Espace::ALL.map(|e| e.page()).join(", ")
+            )),
+            Some(espace) => espace
+        };
+        // The pages are in the same directory as the one of the espace
+        let base_url = url.join("./")?;
+        Ok(Self { base_url, espace })
+    }
+    pub fn demo() -> Self {
+        Self::parse(DEMO_URL).expect("The demo URL is valid")
+    }
+
+    /// Page of the espace, which starts the web client
+    pub fn page_url(&self) -> String {
+        format!("{}{}", self.base_url, self.espace.page())
+    }
+    /// URL of a request of the session
+    pub fn appelfonction_url(&self, session_id: u32, numero_ordre: &str) -> String {
+        format!("{}appelfonction/{}/{}/{}", self.base_url, self.espace.genre(), session_id, numero_ordre)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let demo = Instance::demo();
+        assert_eq!((demo.base_url.as_str(), demo.espace), ("https://demo.index-education.net/pronote/", Espace::Eleve));
+        assert_eq!(demo.page_url(), DEMO_URL);
+        // The URL of the FonctionParametres request of the capture
+        assert_eq!(demo.appelfonction_url(888405, "3fa959b13967e0ef176069e01e23c8d7"), "https://demo.index-education.net/pronote/appelfonction/3/888405/3fa959b13967e0ef176069e01e23c8d7");
+
+        let school = Instance::parse(" 0332768e.index-education.net/pronote/viescolaire.html?login=true#accueil ").unwrap();
+        assert_eq!((school.base_url.as_str(), school.espace.genre()), ("https://0332768e.index-education.net/pronote/", 13));
+        let mobile = Instance::parse("http://pronote.lycee.fr:8080/pronote/Mobile.Parent.html").unwrap();
+        assert_eq!((mobile.page_url().as_str(), mobile.espace.genre()), ("http://pronote.lycee.fr:8080/pronote/parent.html", 2));
+        assert_eq!(Instance::parse("https://lycee.fr/professeur.html").unwrap().base_url.as_str(), "https://lycee.fr/");
+
+        assert!(Instance::parse("https://demo.index-education.net/pronote/").is_err());
+        assert!(Instance::parse("https://demo.index-education.net/pronote/index.html").is_err());
+        assert!(Instance::parse("ftp://demo.index-education.net/pronote/eleve.html").is_err());
+        assert!(Instance::parse("").is_err());
+    }
+
+    #[test]
+    fn espaces() {
+        for espace in Espace::ALL {
+            assert_eq!(Espace::from_page(espace.page()), Some(espace));
+        }
+        assert_eq!(Espace::from_page("mobile.professeur.html").map(|e| e.genre()), Some(1));
+    }
+}
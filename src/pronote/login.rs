@@ -0,0 +1,119 @@
+use std::fmt::Display;
+use super::types::{AuthentificationResponse, Erreur, IdentificationResponse};
+
+/// Why the login to Pronote failed
+#[derive(Debug)]
+pub enum LoginError {
+    /// Wrong identifiant or password
+    BadCredentials,
+    /// The account exists but can't log in, ex: blocked or disabled by the school
+    AccountLocked(String),
+    /// Too many failed logins, the IP address is suspended for a while
+    TooManyAttempts,
+    /// Refused for another reason, with the message of the server
+    Refused(String),
+    Other(anyhow::Error)
+}
+impl From<anyhow::Error> for LoginError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast_ref::<Erreur>() {
+            Some(erreur) if is_suspension(erreur.titre.as_deref().unwrap_or_default()) => Self::TooManyAttempts,
+            _ => Self::Other(e)
+        }
+    }
+}
+impl Display for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadCredentials => write!(f, "Wrong identifiant or password"),
+            Self::AccountLocked(reason) => write!(f, "Account locked: {}", reason),
+            Self::TooManyAttempts => write!(f, "Too many login attempts, try again later"),
+            Self::Refused(reason) => write!(f, "Login refused: {}", reason),
+            Self::Other(e) => write!(f, "{}", e)
+        }
+    }
+}
+impl std::error::Error for LoginError {}
+
+/// Messages of the server when the IP address is suspended after failed logins
+fn is_suspension(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("suspendu") || message.contains("tentatives")
+}
+
+/// Key of the user for the challenge and the key of the session (`getCle` in the web client): the identifiant followed by the SHA-256 of the `alea` and the password.
+///
+/// The identifiant and the password are in lowercase if `modeCompLog` and `modeCompMdp` say so
+pub fn user_key(identifiant: &str, password: &str, identification: &IdentificationResponse) -> String {
+    let identifiant = match identification.mode_comp_log {
+        false => identifiant.to_string(),
+        true => identifiant.to_lowercase()
+    };
+    let password = match identification.mode_comp_mdp {
+        false => password.to_string(),
+        true => password.to_lowercase()
+    };
+    let hash = openssl::sha::sha256(format!("{}{}", identification.alea.as_deref().unwrap_or_default(), password).as_bytes());
+    format!("{}{}", identifiant, hex::encode_upper(hash))
+}
+
+/// Error of an `Authentification` response without a key, from its `Acces` (`EGenreErreurAcces`)
+pub fn refusal(response: &AuthentificationResponse) -> LoginError {
+    let message = response.acces_message.as_ref()
+        .map(|m| format!("{} {}", m.titre, m.message).trim().to_string())
+        .unwrap_or_default();
+    match response.acces {
+        // The challenge was solved with a wrong password
+        None | Some(1) => LoginError::BadCredentials,
+        Some(2) => LoginError::AccountLocked("Accès refusé à cet espace".to_string()),
+        Some(6) => LoginError::AccountLocked("Connexion bloquée par l'établissement".to_string()),
+        Some(10) => LoginError::AccountLocked("Compte désactivé".to_string()),
+        Some(9) if is_suspension(&message) => LoginError::TooManyAttempts,
+        Some(9) => LoginError::Refused(message),
+        Some(acces) => LoginError::Refused(format!("Erreur d'accès {}", acces))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn identification(mode_comp_log: u8, mode_comp_mdp: u8) -> IdentificationResponse {
+        serde_json::from_value(json!({
+            "alea": "{6AD5E32E-D36B-C1E1-EFF0-930984332A6E}",
+            "modeCompLog": mode_comp_log,
+            "modeCompMdp": mode_comp_mdp,
+            "challenge": ""
+        })).unwrap()
+    }
+    fn authentification(response: serde_json::Value) -> AuthentificationResponse {
+        serde_json::from_value(response).unwrap()
+    }
+
+    #[test]
+    fn keys() {
+        // The key of the demo account is checked against the capture in the tests of `crypto`
+        let key = user_key("Demonstration", "pronotevs", &identification(1, 0));
+        assert!(key.starts_with("demonstration"));
+        assert_eq!(key, user_key("demonstration", "pronotevs", &identification(0, 0)));
+        assert_ne!(user_key("Demonstration", "PronoteVS", &identification(1, 0)), key);
+        assert_eq!(user_key("Demonstration", "PronoteVS", &identification(1, 1)), key);
+    }
+
+    #[test]
+    fn refusals() {
+        assert!(matches!(refusal(&authentification(json!({}))), LoginError::BadCredentials));
+        assert!(matches!(refusal(&authentification(json!({ "Acces": 6 }))), LoginError::AccountLocked(_)));
+        assert!(matches!(refusal(&authentification(json!({ "Acces": 10 }))), LoginError::AccountLocked(_)));
+        let suspended = json!({ "Acces": 9, "AccesMessage": { "titre": "Trop de tentatives", "message": "Réessayez dans 5 minutes" } });
+        assert!(matches!(refusal(&authentification(suspended)), LoginError::TooManyAttempts));
+        let closed = json!({ "Acces": 9, "AccesMessage": { "titre": "Espace fermé", "message": "Maintenance" } });
+        assert!(matches!(refusal(&authentification(closed)), LoginError::Refused(m) if m == "Espace fermé Maintenance"));
+
+        let erreur = Erreur { kind: Some(25), titre: Some("Votre adresse IP est provisoirement suspendue".to_string()), message: None };
+        let error = anyhow::Error::new(erreur).context("Invalid response to Identification");
+        assert!(matches!(LoginError::from(error), LoginError::TooManyAttempts));
+        assert!(matches!(LoginError::from(anyhow::anyhow!("Timeout")), LoginError::Other(_)));
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::HashMap, io::{Read, Write}};
+use anyhow::{Result, anyhow, Context};
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use serde_json::Value;
+use super::{crypto::Crypto, types::Message};
+
+/// How the `donneesSec` of the messages are protected, from the parameters of the page of the instance.
+///
+/// The demo instance has `sCrA` and `sCoA`, so its messages are in clear, but the other instances usually encrypt and compress them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Security {
+    pub encrypt: bool,
+    /// Only used if the messages are encrypted
+    pub compress: bool
+}
+impl Security {
+    pub fn from_params(params: &HashMap<String, String>) -> Self {
+        let enabled = |name| params.get(name).is_none_or(|value| value != "true");
+        Self {
+            encrypt: enabled("sCrA"),
+            compress: enabled("sCoA")
+        }
+    }
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    // Same level as pako in the web client
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(6));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut inflated).context("Invalid compressed data")?;
+    Ok(inflated)
+}
+
+/// `donneesSec` of a request.
+///
+/// When the messages are encrypted, it is the JSON encrypted in hexadecimal. Before that, the web client compresses the JSON in hexadecimal, not the JSON itself
+pub fn seal(crypto: &Crypto, security: Security, donnees_sec: Value) -> Result<Value> {
+    if !security.encrypt {
+        return Ok(donnees_sec);
+    }
+    let mut data = serde_json::to_string(&donnees_sec)?.into_bytes();
+    if security.compress {
+        data = deflate(hex::encode(data).as_bytes())?;
+    }
+    Ok(Value::String(hex::encode(crypto.encrypt(&data))))
+}
+
+/// `donneesSec` of a response, after checking it answers the request `counter` of the session.
+///
+/// The responses are numbered after their request, and their `donneesSec` is compressed JSON, not in hexadecimal
+pub fn open(crypto: &Crypto, security: Security, session_id: u32, counter: u32, response: Message) -> Result<Value> {
+    if let Some(error) = response.erreur {
+        return Err(error.into());
+    }
+    if response.session != session_id && response.session != 0 {
+        return Err(anyhow!("Response of another session ({} instead of {})", response.session, session_id));
+    }
+    let numero_ordre = crypto.decrypt(&hex::decode(&response.numero_ordre)?).context("Can't decrypt the numeroOrdre of the response")?;
+    let numero_ordre = String::from_utf8(numero_ordre)?;
+    if numero_ordre != (counter + 1).to_string() {
+        return Err(anyhow!("Unexpected numeroOrdre {} in the response of the request {}", numero_ordre, counter));
+    }
+
+    let donnees_sec = match response.donnees_sec {
+        Value::String(encrypted) if security.encrypt => encrypted,
+        donnees_sec => return Ok(donnees_sec)
+    };
+    let mut data = crypto.decrypt(&hex::decode(donnees_sec)?).context("Can't decrypt the donneesSec of the response")?;
+    if security.compress {
+        data = inflate(&data)?;
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::pronote::types::Erreur;
+    use super::*;
+
+    const CAPTURE: &str = include_str!("../../reverse engineering/requetes");
+
+    fn crypto() -> Crypto {
+        let mut crypto = Crypto::with_iv([1; 16]);
+        crypto.switch_iv();
+        crypto
+    }
+    /// Response the server would send to the request `counter`
+    fn response(crypto: &Crypto, counter: u32, donnees_sec: Value) -> Message {
+        Message {
+            session: 12,
+            numero_ordre: crypto.numero_ordre(counter + 1),
+            nom: "Navigation".to_string(),
+            donnees_sec,
+            erreur: None
+        }
+    }
+
+    #[test]
+    fn security() {
+        let params = |page| crate::api::parse_start_params(page).unwrap();
+        assert_eq!(Security::from_params(&params("Start ({h:'1',sCrA:true,sCoA:true,a:3})")), Security { encrypt: false, compress: false });
+        assert_eq!(Security::from_params(&params("Start ({h:'1',a:3})")), Security { encrypt: true, compress: true });
+    }
+
+    #[test]
+    fn requests() {
+        let crypto = crypto();
+        let donnees_sec = json!({ "donnees": { "identifiant": "démonstration" } });
+        let clear = Security { encrypt: false, compress: false };
+        assert_eq!(seal(&crypto, clear, donnees_sec.clone()).unwrap(), donnees_sec);
+
+        let sealed = seal(&crypto, Security { encrypt: true, compress: true }, donnees_sec.clone()).unwrap();
+        let data = inflate(&crypto.decrypt(&hex::decode(sealed.as_str().unwrap()).unwrap()).unwrap()).unwrap();
+        let data = hex::decode(data).unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&data).unwrap(), donnees_sec);
+        let sealed = seal(&crypto, Security { encrypt: true, compress: false }, donnees_sec.clone()).unwrap();
+        let data = crypto.decrypt(&hex::decode(sealed.as_str().unwrap()).unwrap()).unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&data).unwrap(), donnees_sec);
+    }
+
+    #[test]
+    fn responses() {
+        let crypto = crypto();
+        let donnees_sec = json!({ "donnees": { "cle": "00ff" }, "nom": "Navigation" });
+        let security = Security { encrypt: true, compress: true };
+        let data = deflate(donnees_sec.to_string().as_bytes()).unwrap();
+        let encrypted = Value::String(hex::encode(crypto.encrypt(&data)));
+        assert_eq!(open(&crypto, security, 12, 3, response(&crypto, 3, encrypted.clone())).unwrap(), donnees_sec);
+
+        // Answer to another request or session, or a message encrypted with another key
+        assert!(open(&crypto, security, 12, 5, response(&crypto, 3, encrypted.clone())).is_err());
+        assert!(open(&crypto, security, 13, 3, response(&crypto, 3, encrypted.clone())).is_err());
+        let mut other = crypto.clone();
+        other.set_key(b"key");
+        assert!(open(&other, security, 12, 3, response(&crypto, 3, encrypted)).is_err());
+        let erreur = Erreur { kind: Some(1), titre: None, message: None };
+        let error = Message { erreur: Some(erreur), ..response(&crypto, 3, Value::Null) };
+        assert!(open(&crypto, security, 12, 3, error).unwrap_err().is::<Erreur>());
+
+        // Responses in clear of the demo instance
+        let clear = Security { encrypt: false, compress: false };
+        assert_eq!(open(&crypto, clear, 12, 3, response(&crypto, 3, donnees_sec.clone())).unwrap(), donnees_sec);
+    }
+
+    #[test]
+    fn captured_response() {
+        use base64::Engine;
+        // Response to FonctionParametres, numbered with the IV sent in the request
+        let start = CAPTURE.find("Réponse : {").unwrap() + "Réponse : ".len();
+        let end = start + CAPTURE[start..].find("\n}\n").unwrap() + 2;
+        let response = serde_json::from_str::<Message>(&CAPTURE[start..end]).unwrap();
+        let uuid = CAPTURE.lines().find_map(|line| line.trim().strip_prefix("\"Uuid\": \"")).unwrap().trim_end_matches([',', '"']);
+        let mut crypto = Crypto::with_iv(base64::engine::general_purpose::STANDARD.decode(uuid).unwrap().try_into().unwrap());
+        crypto.switch_iv();
+        let clear = Security { encrypt: false, compress: false };
+        let donnees_sec = open(&crypto, clear, 888405, 1, response.clone()).unwrap();
+        assert_eq!(donnees_sec["nom"], "FonctionParametres");
+        assert!(open(&crypto, clear, 888405, 3, response).is_err());
+    }
+}
@@ -0,0 +1,582 @@
+use std::fmt::Display;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{DeserializeOwned, Error}};
+use serde_json::Value;
+
+/// `_T` of a date
+pub const DATE: u8 = 7;
+/// `_T` of a grade or a scale, ex: "14,5"
+pub const NOTE: u8 = 10;
+/// `_T` of HTML text
+pub const HTML: u8 = 21;
+/// `_T` of a `ListeElements`, or of a single element
+pub const ELEMENTS: u8 = 24;
+
+/// Typed value of the Pronote JSON, ex: `{ "_T": 7, "V": "19/09/2022" }`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Typed<T> {
+    #[serde(rename = "_T")]
+    pub kind: u8,
+    #[serde(rename = "V")]
+    pub value: T
+}
+
+/// List of the web client (`ObjetListeElements`), elements are `Element` unless they have more fields
+pub type ListeElements<T = Element> = Typed<Vec<T>>;
+
+/// Fields shared by most of the elements
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Element {
+    /// Label
+    #[serde(rename = "L", default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Id, only meaningful to the server
+    #[serde(rename = "N", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Genre, what the element is
+    #[serde(rename = "G", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<i64>,
+    /// Position
+    #[serde(rename = "P", default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<i64>
+}
+
+/// Date as `{ "_T": 7, "V": "19/09/2022 14:30:00" }`, the time being left out for days
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Date(pub NaiveDateTime);
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Not padded, like the web client
+        Typed { kind: DATE, value: self.0.format("%-d/%-m/%Y %-H:%-M:%-S").to_string() }.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let typed = Typed::<String>::deserialize(deserializer)?;
+        if typed.kind != DATE {
+            return Err(D::Error::custom(format!("Expected a date, got a value of type {}", typed.kind)));
+        }
+        NaiveDateTime::parse_from_str(&typed.value, "%d/%m/%Y %H:%M:%S")
+            .or_else(|_| NaiveDate::parse_from_str(&typed.value, "%d/%m/%Y").map(|date| date.and_time(NaiveTime::MIN)))
+            .map(Date)
+            .map_err(|_| D::Error::custom(format!("Invalid date : {:?}", typed.value)))
+    }
+}
+
+/// Flags sent as 0 or 1
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(u8::deserialize(deserializer)? != 0)
+}
+
+/// Whole message of `appelfonction`, its `donneesSec` being encrypted or not
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Message<T = Value> {
+    pub session: u32,
+    #[serde(rename = "numeroOrdre")]
+    pub numero_ordre: String,
+    pub nom: String,
+    #[serde(rename = "donneesSec", default)]
+    pub donnees_sec: T,
+    /// Sent by the server instead of the response
+    #[serde(rename = "Erreur", default, skip_serializing_if = "Option::is_none")]
+    pub erreur: Option<Erreur>
+}
+
+/// Error of the server, shown by the web client on its end of session page
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Erreur {
+    #[serde(rename = "G", default)]
+    pub kind: Option<i64>,
+    #[serde(rename = "Titre", default)]
+    pub titre: Option<String>,
+    #[serde(rename = "Message", default)]
+    pub message: Option<String>
+}
+impl Display for Erreur {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error {} of the server : {} {}", self.kind.unwrap_or_default(), self.titre.as_deref().unwrap_or_default(), self.message.as_deref().unwrap_or_default())
+    }
+}
+impl std::error::Error for Erreur {}
+
+/// `donneesSec` of a message in clear
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DonneesSec<T> {
+    #[serde(default = "none", skip_serializing_if = "Option::is_none")]
+    pub donnees: Option<T>,
+    /// Name of the function, only in the responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nom: Option<String>,
+    /// The page of the web client in the requests, notifications in the responses
+    #[serde(rename = "_Signature_", default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Value>
+}
+// `#[serde(default)]` would require `T: Default`
+fn none<T>() -> Option<T> {
+    None
+}
+
+/// Request of `appelfonction`, with the type of its `donnees` in response
+pub trait Request: Serialize {
+    const NAME: &'static str;
+    type Response: DeserializeOwned;
+    /// Page (`onglet`) of the web client the request is sent from, for the ones which depend on it
+    fn onglet(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Onglet of the home page
+pub const ONGLET_ACCUEIL: u32 = 7;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FonctionParametres {
+    /// Temporary IV, see `Crypto::serialize_iv`
+    #[serde(rename = "Uuid")]
+    pub uuid: String,
+    /// Id of the browser, stored by the web client
+    #[serde(rename = "identifiantNav")]
+    pub identifiant_nav: String
+}
+impl Request for FonctionParametres {
+    const NAME: &'static str = "FonctionParametres";
+    type Response = FonctionParametresResponse;
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct FonctionParametresResponse {
+    /// Name of the espace, ex: "Espace Élèves"
+    #[serde(rename = "Nom")]
+    pub nom: String,
+    #[serde(rename = "DateServeurHttp")]
+    pub date_serveur: Option<Date>,
+    #[serde(rename = "General")]
+    pub general: General
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct General {
+    pub version: String,
+    #[serde(rename = "NomEtablissement")]
+    pub nom_etablissement: String,
+    /// ex: "2022-2023"
+    #[serde(rename = "AnneeScolaire")]
+    pub annee_scolaire: String,
+    /// Monday of the first week of the year
+    #[serde(rename = "PremierLundi")]
+    pub premier_lundi: Date,
+    #[serde(rename = "PremiereDate")]
+    pub premiere_date: Date,
+    #[serde(rename = "DerniereDate")]
+    pub derniere_date: Date,
+    /// Number of places of the timetable in a day, a place being a slot of the lessons
+    #[serde(rename = "PlacesParJour")]
+    pub places_par_jour: u32,
+    #[serde(rename = "PlacesParHeure")]
+    pub places_par_heure: u32,
+    /// Start of each place, ex: "08h00"
+    #[serde(rename = "ListeHeures")]
+    pub liste_heures: ListeElements,
+    #[serde(rename = "ListePeriodes", default)]
+    pub liste_periodes: Vec<Periode>
+}
+/// Trimester or semester
+#[derive(Deserialize, Clone, Debug)]
+pub struct Periode {
+    #[serde(rename = "L")]
+    pub label: String,
+    #[serde(rename = "N")]
+    pub id: String,
+    #[serde(rename = "dateDebut")]
+    pub date_debut: Date,
+    #[serde(rename = "dateFin")]
+    pub date_fin: Date
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Identification {
+    #[serde(rename = "genreConnexion")]
+    pub genre_connexion: u8,
+    #[serde(rename = "genreEspace")]
+    pub genre_espace: u8,
+    pub identifiant: String,
+    #[serde(rename = "pourENT")]
+    pub pour_ent: bool,
+    #[serde(rename = "enConnexionAuto")]
+    pub en_connexion_auto: bool,
+    #[serde(rename = "demandeConnexionAuto")]
+    pub demande_connexion_auto: bool,
+    #[serde(rename = "demandeConnexionAppliMobile")]
+    pub demande_connexion_appli_mobile: bool,
+    #[serde(rename = "demandeConnexionAppliMobileJeton")]
+    pub demande_connexion_appli_mobile_jeton: bool,
+    #[serde(rename = "uuidAppliMobile")]
+    pub uuid_appli_mobile: String,
+    #[serde(rename = "loginTokenSAV")]
+    pub login_token_sav: String
+}
+impl Identification {
+    /// Login with a password in the espace `genre_espace`
+    pub fn new(identifiant: String, genre_espace: u8) -> Self {
+        Self {
+            genre_connexion: 0,
+            genre_espace,
+            identifiant,
+            pour_ent: false,
+            en_connexion_auto: false,
+            demande_connexion_auto: false,
+            demande_connexion_appli_mobile: false,
+            demande_connexion_appli_mobile_jeton: false,
+            uuid_appli_mobile: String::new(),
+            login_token_sav: String::new()
+        }
+    }
+}
+impl Request for Identification {
+    const NAME: &'static str = "Identification";
+    type Response = IdentificationResponse;
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdentificationResponse {
+    /// Salt of the password
+    #[serde(default)]
+    pub alea: Option<String>,
+    /// The identifiant is in lowercase in the key of the user
+    #[serde(rename = "modeCompLog", deserialize_with = "flag", default)]
+    pub mode_comp_log: bool,
+    /// The password is in lowercase in the key of the user
+    #[serde(rename = "modeCompMdp", deserialize_with = "flag", default)]
+    pub mode_comp_mdp: bool,
+    /// Encrypted with the key of the user, see `Crypto::solve_challenge`
+    pub challenge: String
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Authentification {
+    pub connexion: u8,
+    /// Solved challenge
+    pub challenge: String,
+    /// `genreEspace`
+    pub espace: u8
+}
+impl Request for Authentification {
+    const NAME: &'static str = "Authentification";
+    type Response = AuthentificationResponse;
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthentificationResponse {
+    /// Name of the user
+    #[serde(rename = "libelleUtil")]
+    pub libelle_util: Option<String>,
+    /// Key of the session, see `Crypto::session_key`, missing if the authentication failed
+    pub cle: Option<String>,
+    #[serde(rename = "derniereConnexion")]
+    pub derniere_connexion: Option<Date>,
+    /// Why the access was refused (`EGenreErreurAcces`)
+    #[serde(rename = "Acces")]
+    pub acces: Option<u8>,
+    /// Message of the refusal, with `Acces` = 9
+    #[serde(rename = "AccesMessage")]
+    pub acces_message: Option<AccesMessage>
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct AccesMessage {
+    #[serde(default)]
+    pub titre: String,
+    #[serde(default)]
+    pub message: String
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ParametresUtilisateur;
+impl Request for ParametresUtilisateur {
+    const NAME: &'static str = "ParametresUtilisateur";
+    type Response = ParametresUtilisateurResponse;
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct ParametresUtilisateurResponse {
+    pub ressource: Ressource,
+    /// Pages the user can open
+    #[serde(rename = "listeOnglets", default)]
+    pub liste_onglets: Vec<Onglet>
+}
+/// The user, or the student for a parent
+#[derive(Deserialize, Clone, Debug)]
+pub struct Ressource {
+    #[serde(rename = "L")]
+    pub label: String,
+    #[serde(rename = "N")]
+    pub id: String,
+    #[serde(rename = "Etablissement")]
+    pub etablissement: Typed<Element>,
+    #[serde(rename = "classeDEleve")]
+    pub classe: Option<Element>,
+    #[serde(rename = "listeGroupes")]
+    pub groupes: Option<ListeElements>
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Onglet {
+    #[serde(rename = "G")]
+    pub id: u32,
+    #[serde(rename = "Onglet", default)]
+    pub onglets: Vec<Onglet>
+}
+
+/// Sent when the user opens a page
+#[derive(Serialize, Clone, Debug)]
+pub struct Navigation {
+    pub onglet: u32,
+    #[serde(rename = "ongletPrec")]
+    pub onglet_prec: u32
+}
+impl Request for Navigation {
+    const NAME: &'static str = "Navigation";
+    type Response = NavigationResponse;
+    fn onglet(&self) -> Option<u32> {
+        Some(self.onglet)
+    }
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct NavigationResponse {}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Semaine {
+    #[serde(rename = "numeroSemaine")]
+    pub numero_semaine: u32
+}
+#[derive(Serialize, Clone, Debug)]
+pub struct Jour {
+    pub date: Date
+}
+/// Content of the widgets of the home page
+#[derive(Serialize, Clone, Debug)]
+pub struct PageAccueil {
+    #[serde(rename = "avecConseilDeClasse")]
+    pub avec_conseil_de_classe: bool,
+    #[serde(rename = "dateGrille")]
+    pub date_grille: Date,
+    #[serde(rename = "numeroSemaine")]
+    pub numero_semaine: u32,
+    #[serde(rename = "coursNonAssures")]
+    pub cours_non_assures: Semaine,
+    #[serde(rename = "personnelsAbsents")]
+    pub personnels_absents: Semaine,
+    pub incidents: Semaine,
+    pub exclusions: Semaine,
+    #[serde(rename = "donneesVS")]
+    pub donnees_vs: Semaine,
+    #[serde(rename = "registreAppel")]
+    pub registre_appel: Jour,
+    #[serde(rename = "previsionnelAbsServiceAnnexe")]
+    pub previsionnel_abs_service_annexe: Jour,
+    #[serde(rename = "donneesProfs")]
+    pub donnees_profs: Semaine,
+    #[serde(rename = "EDT")]
+    pub edt: Semaine,
+    #[serde(rename = "menuDeLaCantine")]
+    pub menu_de_la_cantine: Jour,
+    #[serde(rename = "TAFARendre")]
+    pub taf_a_rendre: Jour,
+    #[serde(rename = "TAFEtActivites")]
+    pub taf_et_activites: Jour,
+    #[serde(rename = "partenaireCDI")]
+    pub partenaire_cdi: Value,
+    #[serde(rename = "tableauDeBord")]
+    pub tableau_de_bord: Jour
+}
+impl PageAccueil {
+    /// Home page on `date`, in the week `numero_semaine` of the school year
+    pub fn new(date: NaiveDateTime, numero_semaine: u32) -> Self {
+        let semaine = || Semaine { numero_semaine };
+        let jour = || Jour { date: Date(date) };
+        Self {
+            avec_conseil_de_classe: true,
+            date_grille: Date(date),
+            numero_semaine,
+            cours_non_assures: semaine(),
+            personnels_absents: semaine(),
+            incidents: semaine(),
+            exclusions: semaine(),
+            donnees_vs: semaine(),
+            registre_appel: jour(),
+            previsionnel_abs_service_annexe: jour(),
+            donnees_profs: semaine(),
+            edt: semaine(),
+            menu_de_la_cantine: jour(),
+            taf_a_rendre: jour(),
+            taf_et_activites: jour(),
+            partenaire_cdi: serde_json::json!({ "CDI": {} }),
+            tableau_de_bord: jour()
+        }
+    }
+}
+impl Request for PageAccueil {
+    const NAME: &'static str = "PageAccueil";
+    type Response = PageAccueilResponse;
+    fn onglet(&self) -> Option<u32> {
+        Some(ONGLET_ACCUEIL)
+    }
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct PageAccueilResponse {
+    #[serde(rename = "dateSelectionnee")]
+    pub date_selectionnee: Option<Date>,
+    /// Lessons of the day
+    #[serde(rename = "ListeCours", default)]
+    pub liste_cours: Vec<Cours>,
+    #[serde(rename = "travailAFaire")]
+    pub travail_a_faire: Option<TravailAFaire>,
+    pub notes: Option<Notes>
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Cours {
+    #[serde(rename = "N")]
+    pub id: String,
+    /// First place of the lesson in the timetable
+    pub place: u32,
+    /// Number of places
+    pub duree: u32,
+    #[serde(rename = "DateDuCours")]
+    pub date: Date,
+    #[serde(rename = "CouleurFond")]
+    pub couleur: Option<String>,
+    /// Subject, teachers and rooms
+    #[serde(rename = "ListeContenus")]
+    pub contenus: ListeElements,
+    #[serde(rename = "estAnnule", default)]
+    pub annule: bool
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct TravailAFaire {
+    #[serde(rename = "listeTAF")]
+    pub liste: ListeElements<Travail>
+}
+/// Homework
+#[derive(Deserialize, Clone, Debug)]
+pub struct Travail {
+    #[serde(rename = "N")]
+    pub id: String,
+    pub matiere: Typed<Element>,
+    pub descriptif: Typed<String>,
+    #[serde(rename = "pourLe")]
+    pub pour_le: Date,
+    #[serde(rename = "donneLe")]
+    pub donne_le: Date,
+    #[serde(rename = "couleurFond")]
+    pub couleur: Option<String>,
+    #[serde(rename = "TAFFait", default)]
+    pub fait: bool
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct Notes {
+    #[serde(rename = "listeDevoirs")]
+    pub liste_devoirs: ListeElements<Devoir>
+}
+/// Graded test
+#[derive(Deserialize, Clone, Debug)]
+pub struct Devoir {
+    #[serde(rename = "N")]
+    pub id: String,
+    pub note: Typed<String>,
+    pub bareme: Typed<String>,
+    pub date: Date,
+    pub service: Typed<Element>
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    const CAPTURE: &str = include_str!("../../reverse engineering/requetes");
+
+    /// Messages of the capture, requests and responses in order
+    fn captured() -> Vec<Message> {
+        let mut starts = CAPTURE.match_indices(" : {\n").map(|(i, _)| i + 3).collect::<Vec<_>>();
+        starts.push(CAPTURE.len());
+        starts.windows(2)
+            .map(|bounds| {
+                // Up to the last closing brace before the next message
+                let end = bounds[0] + CAPTURE[bounds[0]..bounds[1]].rfind("\n}").unwrap() + 2;
+                serde_json::from_str(&CAPTURE[bounds[0]..end]).unwrap()
+            })
+            .collect()
+    }
+    /// `donnees` of the message `nom` of the capture, the request if `response` is false
+    fn donnees(nom: &str, response: bool) -> Value {
+        let messages = captured();
+        let message = messages.iter()
+            .filter(|m| m.nom == nom)
+            .nth(response as usize)
+            .unwrap();
+        message.donnees_sec["donnees"].clone()
+    }
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 9, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn dates() {
+        let date = serde_json::from_value::<Date>(json!({ "_T": 7, "V": "19/09/2022 14:30:00" })).unwrap();
+        assert_eq!(date, Date(at(19, 14, 30)));
+        let day = serde_json::from_value::<Date>(json!({ "_T": 7, "V": "19/09/2022" })).unwrap();
+        assert_eq!(day, Date(at(19, 0, 0)));
+        assert_eq!(serde_json::to_value(Date(at(19, 18, 0))).unwrap(), json!({ "_T": 7, "V": "19/9/2022 18:0:0" }));
+        assert!(serde_json::from_value::<Date>(json!({ "_T": 24, "V": "19/09/2022" })).is_err());
+        assert!(serde_json::from_value::<Date>(json!({ "_T": 7, "V": "2022-09-19" })).is_err());
+    }
+
+    #[test]
+    fn messages() {
+        let messages = captured();
+        assert_eq!(messages.len(), 18);
+        assert!(messages.iter().all(|m| m.session == 888405 && m.erreur.is_none()));
+        let page = serde_json::from_value::<DonneesSec<Value>>(messages[12].donnees_sec.clone()).unwrap();
+        assert_eq!(page.signature, Some(json!({ "onglet": ONGLET_ACCUEIL })));
+        let polling = serde_json::from_value::<DonneesSec<Value>>(messages[11].donnees_sec.clone()).unwrap();
+        assert!(polling.donnees.is_some() && polling.nom.is_none());
+    }
+
+    #[test]
+    fn requests() {
+        let fonction_parametres = FonctionParametres { uuid: "7usBrnumgvtMgxr+6ZJNfA==".to_string(), identifiant_nav: String::new() };
+        assert_eq!(serde_json::to_value(fonction_parametres).unwrap()["Uuid"], donnees("FonctionParametres", false)["Uuid"]);
+        assert_eq!(serde_json::to_value(Identification::new("demonstration".to_string(), 3)).unwrap(), donnees("Identification", false));
+        let navigation = Navigation { onglet: ONGLET_ACCUEIL, onglet_prec: ONGLET_ACCUEIL };
+        assert_eq!(serde_json::to_value(navigation).unwrap(), donnees("Navigation", false));
+        assert_eq!(serde_json::to_value(PageAccueil::new(at(19, 18, 0), 4)).unwrap(), donnees("PageAccueil", false));
+        assert!(serde_json::to_value(ParametresUtilisateur).unwrap().is_null());
+    }
+
+    #[test]
+    fn responses() {
+        let parametres = serde_json::from_value::<FonctionParametresResponse>(donnees("FonctionParametres", true)).unwrap();
+        assert_eq!(parametres.nom, "Espace Élèves");
+        assert_eq!(parametres.general.premier_lundi, Date(NaiveDate::from_ymd_opt(2022, 8, 29).unwrap().and_time(NaiveTime::MIN)));
+        assert_eq!(parametres.general.liste_heures.value[1].label.as_deref(), Some("08h30"));
+        assert_eq!(parametres.general.liste_periodes[0].label, "Trimestre 1");
+
+        let identification = serde_json::from_value::<IdentificationResponse>(donnees("Identification", true)).unwrap();
+        assert_eq!(identification.alea.as_deref(), Some("{6AD5E32E-D36B-C1E1-EFF0-930984332A6E}"));
+        assert!(identification.mode_comp_log && !identification.mode_comp_mdp);
+
+        let authentification = serde_json::from_value::<AuthentificationResponse>(donnees("Authentification", true)).unwrap();
+        assert_eq!(authentification.libelle_util.as_deref(), Some("PARENT Fanny"));
+        assert!(authentification.cle.is_some());
+
+        let utilisateur = serde_json::from_value::<ParametresUtilisateurResponse>(donnees("ParametresUtilisateur", true)).unwrap();
+        assert_eq!(utilisateur.ressource.etablissement.value.label.as_deref(), Some("SITE DE DEMONSTRATION"));
+        assert_eq!(utilisateur.ressource.classe.and_then(|c| c.label).as_deref(), Some("3A"));
+        assert!(utilisateur.liste_onglets.iter().any(|o| o.id == ONGLET_ACCUEIL));
+
+        serde_json::from_value::<NavigationResponse>(donnees("Navigation", true)).unwrap();
+
+        let accueil = serde_json::from_value::<PageAccueilResponse>(donnees("PageAccueil", true)).unwrap();
+        assert_eq!(accueil.date_selectionnee, Some(Date(at(19, 0, 0))));
+        assert_eq!(accueil.liste_cours.len(), 6);
+        assert!(accueil.liste_cours.iter().any(|c| c.date == Date(at(19, 14, 30))));
+        let travail = &accueil.travail_a_faire.unwrap().liste.value[0];
+        assert_eq!((travail.matiere.value.label.as_deref(), travail.pour_le), (Some("ANGLAIS LV1"), Date(at(20, 0, 0))));
+        assert_eq!(travail.descriptif.kind, HTML);
+        let devoir = &accueil.notes.unwrap().liste_devoirs.value[0];
+        assert_eq!((devoir.note.kind, devoir.note.value.as_str(), devoir.bareme.value.as_str()), (NOTE, "19", "20"));
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt::Display;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use http_bytes::http;
+
+const MAX_HEADERS: usize = 64;
+
+/// Size limits applied while reading a request
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Maximum size of the request line and headers (answered with 431)
+    pub max_head_size: usize,
+    /// Maximum size of the decoded body (answered with 413)
+    pub max_body_size: usize
+}
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_head_size: 8 * 1024,
+            max_body_size: 1024 * 1024
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+    /// The client closed the connection before sending anything
+    Closed,
+    BadRequest(String),
+    HeadersTooLarge,
+    BodyTooLarge,
+    /// The request wasn't received in time
+    Timeout
+}
+impl From<std::io::Error> for ReadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Closed => write!(f, "Connection closed"),
+            Self::BadRequest(e) => write!(f, "Bad request: {}", e),
+            Self::HeadersTooLarge => write!(f, "Request headers too large"),
+            Self::BodyTooLarge => write!(f, "Request body too large"),
+            Self::Timeout => write!(f, "Request timeout")
+        }
+    }
+}
+
+/// Reads HTTP/1.x requests from a stream.
+///
+/// Bytes received after the end of a request are kept in the buffer so they can be used by the next one.
+pub struct RequestReader<S> {
+    stream: S,
+    buf: Vec<u8>,
+    limits: Limits
+}
+impl<S: AsyncRead + AsyncWrite + Unpin> RequestReader<S> {
+    pub fn new(stream: S, limits: Limits) -> Self {
+        Self {
+            stream,
+            buf: Vec::new(),
+            limits
+        }
+    }
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Read more bytes from the stream into the buffer. Returns the number of bytes read (0 on EOF)
+    async fn read_more(&mut self) -> Result<usize, ReadError> {
+        self.buf.reserve(1024);
+        Ok(self.stream.read_buf(&mut self.buf).await?)
+    }
+    /// Make sure that at least `len` bytes are in the buffer
+    async fn fill_to(&mut self, len: usize) -> Result<(), ReadError> {
+        while self.buf.len() < len {
+            if self.read_more().await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+        Ok(())
+    }
+    /// Read a line terminated by CRLF and remove it from the buffer
+    async fn read_line(&mut self) -> Result<String, ReadError> {
+        loop {
+            if let Some(pos) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                let line = self.buf.drain(..pos + 2).take(pos).collect::<Vec<_>>();
+                return String::from_utf8(line).map_err(|_| ReadError::BadRequest("Invalid chunk line".to_string()));
+            }
+            if self.buf.len() > self.limits.max_head_size {
+                return Err(ReadError::BadRequest("Chunk line too long".to_string()));
+            }
+            if self.read_more().await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+
+    /// Wait until the first bytes of the next request are available
+    pub async fn wait_for_request(&mut self) -> Result<(), ReadError> {
+        if self.buf.is_empty() && self.read_more().await? == 0 {
+            return Err(ReadError::Closed);
+        }
+        Ok(())
+    }
+
+    pub async fn read_request(&mut self) -> Result<http::Request<Option<String>>, ReadError> {
+        // Head
+        let (builder, head_len) = loop {
+            let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+            let mut header_req = httparse::Request::new(&mut headers);
+            match header_req.parse(&self.buf) {
+                Ok(httparse::Status::Complete(len)) => {
+                    if len > self.limits.max_head_size {
+                        return Err(ReadError::HeadersTooLarge);
+                    }
+                    let mut builder = http::request::Builder::new();
+                    builder
+                        .method(header_req.method.unwrap())
+                        .uri(header_req.path.unwrap())
+                        .version(match header_req.version {
+                            Some(0) => http::Version::HTTP_10,
+                            _ => http::Version::HTTP_11
+                        });
+                    for header in header_req.headers.iter() {
+                        builder.header(header.name, header.value);
+                    }
+                    break (builder, len);
+                },
+                Ok(httparse::Status::Partial) => {
+                    if self.buf.len() > self.limits.max_head_size {
+                        return Err(ReadError::HeadersTooLarge);
+                    }
+                },
+                Err(httparse::Error::TooManyHeaders) => return Err(ReadError::HeadersTooLarge),
+                Err(e) => return Err(ReadError::BadRequest(e.to_string()))
+            }
+            if self.read_more().await? == 0 {
+                if self.buf.is_empty() {
+                    return Err(ReadError::Closed);
+                }
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        };
+        self.buf.drain(..head_len);
+
+        let mut builder = builder;
+        let req = builder.body(()).map_err(|e| ReadError::BadRequest(e.to_string()))?;
+        let headers = req.headers();
+
+        let chunked = headers.get_all(http::header::TRANSFER_ENCODING).iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.split(',').any(|e| e.trim().eq_ignore_ascii_case("chunked")));
+        let content_length = match headers.get(http::header::CONTENT_LENGTH) {
+            None => None,
+            Some(v) => match v.to_str().ok().and_then(|v| v.trim().parse::<usize>().ok()) {
+                None => return Err(ReadError::BadRequest("Invalid content-length".to_string())),
+                Some(l) => Some(l)
+            }
+        };
+        let expect_continue = headers.get(http::header::EXPECT)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+
+        if let Some(len) = content_length {
+            if !chunked && len > self.limits.max_body_size {
+                return Err(ReadError::BodyTooLarge);
+            }
+        }
+        if expect_continue && (chunked || content_length.is_some()) && self.buf.is_empty() {
+            self.stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+            self.stream.flush().await?;
+        }
+
+        // Body
+        let body = if chunked {
+            Some(self.read_chunked_body().await?)
+        } else if let Some(len) = content_length {
+            self.fill_to(len).await?;
+            Some(self.buf.drain(..len).collect::<Vec<_>>())
+        } else {
+            None
+        };
+        let body = match body {
+            None => None,
+            Some(b) => match String::from_utf8(b) {
+                Ok(b) => Some(b),
+                Err(_) => return Err(ReadError::BadRequest("Body is not valid UTF-8".to_string()))
+            }
+        };
+
+        let (parts, _) = req.into_parts();
+        Ok(http::Request::from_parts(parts, body))
+    }
+
+    async fn read_chunked_body(&mut self) -> Result<Vec<u8>, ReadError> {
+        let mut body = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            // Ignore chunk extensions
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = match usize::from_str_radix(size, 16) {
+                Ok(s) => s,
+                Err(_) => return Err(ReadError::BadRequest(format!("Invalid chunk size : {:?}", size)))
+            };
+            if size == 0 {
+                // Skip trailers
+                while !self.read_line().await?.is_empty() {}
+                return Ok(body);
+            }
+            // A huge chunk size must not overflow
+            if body.len().checked_add(size).is_none_or(|len| len > self.limits.max_body_size) {
+                return Err(ReadError::BodyTooLarge);
+            }
+            self.fill_to(size + 2).await?;
+            body.extend(self.buf.drain(..size));
+            if self.buf.drain(..2).as_slice() != b"\r\n" {
+                return Err(ReadError::BadRequest("Missing CRLF after chunk".to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, DuplexStream};
+    use super::*;
+
+    /// Reader of a stream on which `data` was sent
+    async fn reader(data: &[u8], limits: Limits) -> RequestReader<DuplexStream> {
+        let (mut client, server) = duplex(64 * 1024);
+        client.write_all(data).await.unwrap();
+        // Nothing more is sent, but the reader may answer 100 Continue
+        client.shutdown().await.unwrap();
+        tokio::spawn(async move {
+            let mut sink = Vec::new();
+            let _ = client.read_to_end(&mut sink).await;
+        });
+        RequestReader::new(server, limits)
+    }
+    async fn read(data: &[u8], limits: Limits) -> Result<http::Request<Option<String>>, ReadError> {
+        reader(data, limits).await.read_request().await
+    }
+
+    #[tokio::test]
+    async fn requests() {
+        let req = read("PUT /api/create_user?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: 12\r\n\r\nname=élève".as_bytes(), Limits::default()).await.unwrap();
+        assert_eq!((req.method(), req.uri().path(), req.uri().query()), (&http::Method::PUT, "/api/create_user", Some("x=1")));
+        assert_eq!(req.headers()["host"], "localhost");
+        assert_eq!(req.body().as_deref(), Some("name=élève"));
+        let req = read(b"GET / HTTP/1.0\r\n\r\n", Limits::default()).await.unwrap();
+        assert_eq!((req.version(), req.body()), (http::Version::HTTP_10, &None));
+
+        assert!(matches!(read(b"GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n", Limits::default()).await, Err(ReadError::BadRequest(_))));
+        assert!(matches!(read(b"GET / HTTP/1.1\r\nContent-Length: 2\r\n\r\n\xff\xfe", Limits::default()).await, Err(ReadError::BadRequest(_))));
+        assert!(matches!(read(b"NOT HTTP\r\n\r\n", Limits::default()).await, Err(ReadError::BadRequest(_))));
+        // The body is shorter than announced
+        assert!(matches!(read(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc", Limits::default()).await, Err(ReadError::Io(_))));
+        assert!(matches!(read(b"", Limits::default()).await, Err(ReadError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let limits = Limits { max_head_size: 64, max_body_size: 4 };
+        let long_header = format!("GET / HTTP/1.1\r\nx-long: {}\r\n\r\n", "a".repeat(100));
+        assert!(matches!(read(long_header.as_bytes(), limits).await, Err(ReadError::HeadersTooLarge)));
+        // Even when the end of the head wasn't received yet
+        let partial = format!("GET / HTTP/1.1\r\nx-long: {}", "a".repeat(100));
+        assert!(matches!(read(partial.as_bytes(), limits).await, Err(ReadError::HeadersTooLarge)));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "a: b\r\n".repeat(MAX_HEADERS + 1));
+        assert!(matches!(read(many_headers.as_bytes(), Limits::default()).await, Err(ReadError::HeadersTooLarge)));
+
+        // Refused before reading the body
+        assert!(matches!(read(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n", limits).await, Err(ReadError::BodyTooLarge)));
+        assert_eq!(read(b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nabcd", limits).await.unwrap().body().as_deref(), Some("abcd"));
+    }
+
+    #[tokio::test]
+    async fn pipelining() {
+        let data = b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcGET /b HTTP/1.1\r\n\r\nPOST /c HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nd\r\n0\r\n\r\n";
+        let mut reader = reader(data, Limits::default()).await;
+        let mut requests = Vec::new();
+        loop {
+            match reader.wait_for_request().await {
+                Err(ReadError::Closed) => break,
+                result => result.unwrap()
+            }
+            let req = reader.read_request().await.unwrap();
+            requests.push((req.uri().path().to_string(), req.into_body()));
+        }
+        assert_eq!(requests, [
+            ("/a".to_string(), Some("abc".to_string())),
+            ("/b".to_string(), None),
+            ("/c".to_string(), Some("d".to_string()))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn chunked_body() {
+        let req = read(b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\ntrailer: x\r\n\r\n", Limits::default()).await;
+        assert_eq!(req.unwrap().body().as_deref(), Some("hello world"));
+
+        let limits = Limits { max_body_size: 8, ..Default::default() };
+        let req = read(b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n", limits).await;
+        assert!(matches!(req, Err(ReadError::BodyTooLarge)));
+        // The size of the second chunk added to the first one overflows
+        let huge = format!("POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n1\r\na\r\n{:x}\r\n", usize::MAX);
+        assert!(matches!(read(huge.as_bytes(), Limits::default()).await, Err(ReadError::BodyTooLarge)));
+        let req = read(b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n1ffffffffffffffff\r\n", Limits::default()).await;
+        assert!(matches!(req, Err(ReadError::BadRequest(_))));
+    }
+}
@@ -0,0 +1,381 @@
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+use http_bytes::{http, http::{Method, StatusCode}};
+use crate::{assets::Assets, db::{DB, User}, hot_reload, permissions::Permissions, scss, sessions, HandleError, HttpArgs, TemplateContext};
+
+pub type HandlerResult = Result<Option<http::Response<Vec<u8>>>, HandleError>;
+type BoxedHandler = Box<dyn Fn(RequestContext) -> Pin<Box<dyn Future<Output = HandlerResult> + Send>> + Send + Sync>;
+
+/// Everything a handler needs to answer a request
+pub struct RequestContext {
+    pub req: http::Request<Option<String>>,
+    /// Parameters captured in the path (`:id` in `/api/classes/:id/homeworks`)
+    pub params: HashMap<String, String>,
+    /// Parameters from the query string
+    pub args: HttpArgs,
+    pub assets: Arc<Assets>,
+    pub db: Arc<DB>,
+    pub dev_mode: bool,
+    /// The logged in user
+    pub user: Option<User>,
+    pub permissions: Permissions
+}
+impl RequestContext {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|p| p.as_str())
+    }
+    /// Parse the body as an url encoded form
+    pub fn form(&self) -> HttpArgs {
+        HttpArgs::from_urlencoded(self.req.body().as_deref().unwrap_or(""))
+    }
+    /// Context for the page templates
+    pub fn template_context(&self) -> minijinja::Value {
+        minijinja::Value::from_struct_object(TemplateContext {
+            args: self.args.clone(),
+            user: self.user.clone(),
+            permissions: self.permissions.clone()
+        })
+    }
+    /// Whether cookies should only be sent over https
+    pub fn secure_cookies(&self) -> bool {
+        !self.dev_mode
+    }
+    /// Render a template as an html response (used for the fragments swapped by htmx)
+    pub fn render(&self, name: &str, context: minijinja::Value) -> HandlerResult {
+        let template = self.assets.env.get_template(name).map_err(anyhow::Error::from)?;
+        let html = template.render(context).map_err(anyhow::Error::from)?;
+        let res = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(html.into_bytes())
+            .unwrap();
+        Ok(Some(res))
+    }
+    /// Render a page template, with `context` added to the page context
+    pub fn render_page(&self, name: &str, context: minijinja::Value) -> HandlerResult {
+        let template = self.assets.env.get_template(name).map_err(anyhow::Error::from)?;
+        let mut html = template.render(minijinja::context!{ ..context, ..self.template_context() }).map_err(anyhow::Error::from)?;
+        self.add_dev_tools(&mut html);
+        let res = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(html.into_bytes())
+            .unwrap();
+        Ok(Some(res))
+    }
+    /// In dev mode, add the reload script and the errors of the templates and stylesheets to a page
+    pub fn add_dev_tools(&self, html: &mut String) {
+        if !self.dev_mode {
+            return;
+        }
+        if let Some(pos) = html.rfind("</body>") {
+            html.insert_str(pos, hot_reload::RELOAD_SCRIPT);
+            // The server doesn't start if the templates or stylesheets don't compile outside of dev mode
+            if !self.assets.errors.is_empty() {
+                html.insert_str(pos, &scss::error_overlay(&self.assets.errors));
+            }
+        }
+    }
+}
+
+enum Segment {
+    Literal(String),
+    /// `:name`, matches one segment
+    Param(String),
+    /// `*name`, matches all the remaining segments
+    Wildcard(String)
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: BoxedHandler
+}
+impl Route {
+    fn is_catch_all(&self) -> bool {
+        matches!(self.segments.last(), Some(Segment::Wildcard(_)))
+    }
+    fn matches(&self, path: &[&str]) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        let mut path = path.iter();
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Wildcard(name) => {
+                    let rest = path.by_ref().copied().collect::<Vec<_>>().join("/");
+                    params.insert(name.clone(), url_escape::decode(&rest).into_owned());
+                    return Some(params);
+                },
+                Segment::Literal(lit) => {
+                    if path.next() != Some(&lit.as_str()) {
+                        return None;
+                    }
+                },
+                Segment::Param(name) => match path.next() {
+                    None | Some(&"") => return None,
+                    Some(value) => {
+                        params.insert(name.clone(), url_escape::decode(value).into_owned());
+                    }
+                }
+            }
+        }
+        match path.next() {
+            None => Some(params),
+            Some(_) => None
+        }
+    }
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.trim_start_matches('/').split('/').collect()
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>
+}
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for `method` on `pattern`.
+    ///
+    /// Routes are tried in the order they were added.
+    pub fn route<F, Fut>(&mut self, method: Method, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static
+    {
+        let segments = split_path(pattern).into_iter().map(|s| {
+            if let Some(name) = s.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = s.strip_prefix('*') {
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Literal(s.to_string())
+            }
+        }).collect();
+        self.routes.push(Route {
+            method,
+            segments,
+            handler: Box::new(move |ctx| Box::pin(handler(ctx)))
+        });
+        self
+    }
+    pub fn get<F, Fut>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static
+    {
+        self.route(Method::GET, pattern, handler)
+    }
+    pub fn post<F, Fut>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static
+    {
+        self.route(Method::POST, pattern, handler)
+    }
+    pub fn put<F, Fut>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static
+    {
+        self.route(Method::PUT, pattern, handler)
+    }
+    pub fn delete<F, Fut>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(RequestContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static
+    {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    /// Methods accepted on `path`, including the automatic HEAD and OPTIONS.
+    ///
+    /// Catch-all routes (ending with `*name`) are only considered when no other route matches.
+    fn allowed_methods(&self, path: &[&str]) -> Vec<Method> {
+        let mut methods = Vec::new();
+        for catch_all in [false, true] {
+            for route in self.routes.iter() {
+                if route.is_catch_all() == catch_all && !methods.contains(&route.method) && route.matches(path).is_some() {
+                    methods.push(route.method.clone());
+                }
+            }
+            if !methods.is_empty() {
+                break;
+            }
+        }
+        if methods.is_empty() {
+            return methods;
+        }
+        if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+            methods.push(Method::HEAD);
+        }
+        if !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        methods
+    }
+
+    fn find(&self, method: &Method, path: &[&str]) -> Option<(&Route, HashMap<String, String>)> {
+        self.routes.iter()
+            .filter(|r| r.method == *method)
+            .find_map(|r| r.matches(path).map(|params| (r, params)))
+    }
+
+    pub async fn handle(&self, req: http::Request<Option<String>>, assets: Arc<Assets>, db: Arc<DB>, dev_mode: bool) -> HandlerResult {
+        let path = req.uri().path().to_string();
+        let path = split_path(&path);
+        let method = req.method().clone();
+
+        if !self.allowed_methods(&path).contains(&method) {
+            return self.no_route(&method, &path);
+        }
+        // HEAD is answered with the GET handler when there isn't a specific one
+        let (route, params) = match self.find(&method, &path) {
+            Some(r) => r,
+            None if method == Method::HEAD => match self.find(&Method::GET, &path) {
+                Some(r) => r,
+                None => return self.no_route(&method, &path)
+            },
+            None => return self.no_route(&method, &path)
+        };
+
+        let (user, session_cookie) = sessions::current_user(&db, &req, !dev_mode).await?;
+        let permissions = Permissions::load(&db, user.as_ref()).await?;
+        let args = HttpArgs::from_urlencoded(req.uri().query().unwrap_or(""));
+        let ctx = RequestContext { req, params, args, assets, db, dev_mode, user, permissions };
+        let mut res = (route.handler)(ctx).await?;
+
+        // Renew the session cookie, unless the handler changed the session
+        if let Some(cookie) = session_cookie {
+            let res = res.get_or_insert_with(|| http::Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Vec::new())
+                .unwrap());
+            if !res.headers().contains_key(http::header::SET_COOKIE) {
+                res.headers_mut().insert(http::header::SET_COOKIE, http::HeaderValue::from_str(&cookie).unwrap());
+            }
+        }
+
+        if method == Method::HEAD && route.method != Method::HEAD {
+            return Ok(res.map(|res| {
+                let (mut parts, body) = res.into_parts();
+                if !parts.headers.contains_key(http::header::CONTENT_LENGTH) {
+                    parts.headers.insert(http::header::CONTENT_LENGTH, body.len().into());
+                }
+                http::Response::from_parts(parts, Vec::new())
+            }));
+        }
+        Ok(res)
+    }
+
+    /// Answer a request that no route accepts (404, 405 or the automatic OPTIONS)
+    fn no_route(&self, method: &Method, path: &[&str]) -> HandlerResult {
+        let allowed = self.allowed_methods(path);
+        if allowed.is_empty() {
+            return Err(HandleError::NotFound);
+        }
+        let allow = allowed.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ");
+        let status = if method == Method::OPTIONS {
+            StatusCode::NO_CONTENT
+        } else {
+            StatusCode::METHOD_NOT_ALLOWED
+        };
+        let res = http::Response::builder()
+            .status(status)
+            .header(http::header::ALLOW, allow)
+            .body(Vec::new())
+            .unwrap();
+        Ok(Some(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers with the name of the route and its parameters
+    fn echo(name: &'static str) -> impl Fn(RequestContext) -> std::future::Ready<HandlerResult> + Send + Sync {
+        move |ctx| {
+            let mut params = ctx.params.into_iter().collect::<Vec<_>>();
+            params.sort();
+            let body = format!("{} {:?}", name, params);
+            std::future::ready(Ok(Some(http::Response::builder().body(body.into_bytes()).unwrap())))
+        }
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router
+            .get("/api/classes/:id", echo("class"))
+            .post("/api/classes/:id", echo("update class"))
+            .get("/api/classes/:id/members/:user", echo("member"))
+            .get("/api/classes/new", echo("never matched"))
+            .delete("/api/homeworks/:id", echo("delete homework"))
+            .get("/*path", echo("public"));
+        router
+    }
+
+    async fn request(router: &Router, method: Method, path: &str) -> http::Response<Vec<u8>> {
+        let assets = Arc::new(Assets::load(None).await.unwrap());
+        let db = Arc::new(DB::new(None).await.unwrap());
+        let req = http::Request::builder().method(method).uri(path).body(None).unwrap();
+        match router.handle(req, assets, db, false).await {
+            Ok(res) => res.unwrap(),
+            Err(HandleError::NotFound) => http::Response::builder().status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap(),
+            Err(_) => panic!("Unexpected error for {}", path)
+        }
+    }
+    fn body(res: &http::Response<Vec<u8>>) -> &str {
+        std::str::from_utf8(res.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn matching() {
+        let router = router();
+        let res = request(&router, Method::GET, "/api/classes/12").await;
+        assert_eq!(body(&res), r#"class [("id", "12")]"#);
+        // Routes are tried in order, and the parameters are decoded
+        let res = request(&router, Method::GET, "/api/classes/new").await;
+        assert_eq!(body(&res), r#"class [("id", "new")]"#);
+        let res = request(&router, Method::GET, "/api/classes/1%20A/members/7?x=1").await;
+        assert_eq!(body(&res), r#"member [("id", "1 A"), ("user", "7")]"#);
+        let res = request(&router, Method::POST, "/api/classes/12").await;
+        assert_eq!(body(&res), r#"update class [("id", "12")]"#);
+
+        // The wildcard matches the remaining segments, including none
+        let res = request(&router, Method::GET, "/styles/index.css").await;
+        assert_eq!(body(&res), r#"public [("path", "styles/index.css")]"#);
+        let res = request(&router, Method::GET, "/").await;
+        assert_eq!(body(&res), r#"public [("path", "")]"#);
+        let res = request(&router, Method::GET, "/api/classes/").await;
+        assert_eq!(body(&res), r#"public [("path", "api/classes/")]"#);
+    }
+
+    #[tokio::test]
+    async fn methods() {
+        let router = router();
+        let allow = |res: &http::Response<Vec<u8>>| res.headers()[http::header::ALLOW].to_str().unwrap().to_string();
+
+        let res = request(&router, Method::DELETE, "/api/classes/12").await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(allow(&res), "GET, POST, HEAD, OPTIONS");
+        let res = request(&router, Method::OPTIONS, "/api/classes/12").await;
+        assert_eq!((res.status(), allow(&res)), (StatusCode::NO_CONTENT, "GET, POST, HEAD, OPTIONS".to_string()));
+        // The catch-all route isn't allowed on paths having other routes
+        let res = request(&router, Method::GET, "/api/homeworks/3").await;
+        assert_eq!((res.status(), allow(&res)), (StatusCode::METHOD_NOT_ALLOWED, "DELETE, OPTIONS".to_string()));
+        let res = request(&router, Method::POST, "/index.html").await;
+        assert_eq!(allow(&res), "GET, HEAD, OPTIONS");
+
+        // HEAD is answered by the GET route, without the body
+        let res = request(&router, Method::HEAD, "/api/classes/12").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.body().is_empty());
+        assert_eq!(res.headers()[http::header::CONTENT_LENGTH], r#"class [("id", "12")]"#.len().to_string().as_str());
+
+        let mut router = Router::new();
+        router.get("/api/timetable", echo("timetable"));
+        assert_eq!(request(&router, Method::GET, "/api/other").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(request(&router, Method::OPTIONS, "/api/other").await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,1148 @@
+use http_bytes::{http, http::Method};
+use std::collections::HashMap;
+use chrono::{Duration, Local, NaiveDate};
+use minijinja::context;
+use crate::{assets, conflicts::{self, Resources}, dates, db::{self, Class, ClassFilter, DB, HolidayFilter, HomeworkData, HomeworkFilter, Lesson, LessonData, LessonException, LessonFilter, Membership, MembershipAction, MembershipChange, MembershipFilter, Repeat, Role, TrashKind, UserFilter}, password, permissions::Permission, sessions, timetable, router::{Router, RequestContext, HandlerResult}, HandleError, HttpArgs};
+
+pub fn create_router(dev_mode: bool) -> Router {
+    let mut router = Router::new();
+
+    if dev_mode {
+        router.route(Method::GET, "/debug", debug)
+            .route(Method::PUT, "/debug", debug)
+            .route(Method::POST, "/debug", debug);
+    }
+
+    router
+        .put("/api/create_class", create_class)
+        .put("/api/create_user", create_user)
+        .post("/api/logout", logout)
+        .get("/", index)
+        .get("/api/timetable", timetable)
+        .get("/api/homeworks", list_homeworks)
+        .put("/api/homeworks", create_homework)
+        .get("/api/homeworks/form", homework_form)
+        .delete("/api/homeworks/:id", delete_homework)
+        .get("/api/lessons/form", lesson_form)
+        .put("/api/lessons", create_lesson)
+        .get("/api/lessons/:id/form", edit_lesson_form)
+        .post("/api/lessons/:id", update_lesson)
+        .get("/api/lessons/:id/exceptions/:date/form", occurrence_form)
+        .post("/api/lessons/:id/exceptions/:date", save_occurrence)
+        .delete("/api/lessons/:id/exceptions/:date", restore_occurrence)
+        .put("/api/classes/join", join_class)
+        .get("/classes", rosters)
+        .post("/api/classes/:id/join_code", rotate_join_code)
+        .post("/api/classes/:id/members/:user", change_member_role)
+        .delete("/api/classes/:id/members/:user", remove_member)
+        .get("/ressources", resources)
+        .put("/api/rooms", create_room)
+        .post("/api/rooms/:id", rename_room)
+        .delete("/api/rooms/:id", delete_room)
+        .put("/api/teachers", create_teacher)
+        .post("/api/teachers/:id", update_teacher)
+        .delete("/api/teachers/:id", delete_teacher)
+        .put("/api/holidays", create_holiday)
+        .post("/api/holidays/:id", update_holiday)
+        .delete("/api/holidays/:id", delete_holiday)
+        .get("/corbeille", trash)
+        .post("/api/restore", restore)
+        // Must be last as it matches every path
+        .get("/*path", serve_public);
+
+    router
+}
+
+/// Print the request, without the header values or the body which can hold the session cookie or a password
+async fn debug(ctx: RequestContext) -> HandlerResult {
+    let headers = ctx.req.headers().keys().map(|name| name.as_str()).collect::<Vec<_>>();
+    println!("{} {} (headers: {})", ctx.req.method(), ctx.req.uri().path(), headers.join(", "));
+    Ok(None)
+}
+
+/// Serve a file from `public/`, rendering it if it's a template
+async fn serve_public(ctx: RequestContext) -> HandlerResult {
+    let mut path = "/".to_string() + ctx.param("path").unwrap_or("");
+    println!("Got request for: {}", path);
+    if path == "/" {
+        path = "/index.html".to_string();
+    }
+
+    if let Some(file) = ctx.assets.files.get(&path) {
+        return Ok(Some(file.response(&ctx.req)));
+    }
+
+    let template = match ctx.assets.env.get_template(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            if let minijinja::ErrorKind::TemplateNotFound = e.kind() {
+                // Try various extensions
+                match ctx.assets.env.get_template(&(path.to_string() + ".html")) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        // TODO: Check if it's a directory and serve index.html
+                        if let minijinja::ErrorKind::TemplateNotFound = e.kind() {
+                            return Err(HandleError::NotFound);
+                        } else {
+                            return Err(HandleError::InternalServerError(e.into()));
+                        }
+                    }
+                }
+            } else {
+                return Err(HandleError::InternalServerError(e.into()));
+            }
+        }
+    };
+
+    let mut res = match template.render(ctx.template_context()) {
+        Ok(b) => b,
+        Err(e) => return Err(HandleError::InternalServerError(e.into()))
+    };
+    ctx.add_dev_tools(&mut res);
+    let res = res.as_bytes();
+
+    let res = http::Response::builder()
+        .header(http::header::CONTENT_TYPE, assets::content_type(template.name()))
+        .body(res.to_owned())
+        .unwrap();
+
+    Ok(Some(res))
+}
+
+async fn create_class(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::CreateClass)?;
+    let args = ctx.form();
+
+    let name = match args.0.get("name") {
+        None => return Err(HandleError::BadRequest),
+        Some(n) => n.clone()
+    };
+
+    ctx.db.insert_class(name).await?;
+
+    Ok(None)
+}
+
+/// Create the account if the username is free, or log in if it exists
+async fn create_user(ctx: RequestContext) -> HandlerResult {
+    const FORM: &str = "/components/top_bar/adding_popup/user_form.html";
+    let args = ctx.form();
+
+    let name = args.0.get("name").cloned().unwrap_or_default();
+    let password = args.0.get("password").cloned().unwrap_or_default();
+    if name.is_empty() || password.is_empty() {
+        return ctx.render(FORM, context!{ name, error => "Veuillez entrer un nom et un mot de passe" });
+    }
+
+    match ctx.db.get_user_by_name(name.clone()).await? {
+        Some(user) => {
+            if !password::verify(user.password_hash.clone(), password.clone()).await? {
+                return ctx.render(FORM, context!{ name, error => "Mot de passe incorrect" });
+            }
+            if password::needs_rehash(&user.password_hash, &user.name, password::params()) {
+                ctx.db.update_user_password_hash(user.id, password::hash(password, password::params()).await?).await?;
+            }
+            println!("User logged in: {}", name);
+            let cookie = sessions::start(&ctx.db, user.id, ctx.secure_cookies()).await?;
+            logged_in(ctx.render(FORM, context!{ message => format!("Connecté en tant que {}", name) })?, cookie)
+        },
+        None => {
+            // Created by another request in the meantime
+            let id = match ctx.db.insert_user(name.clone(), password::hash(password, password::params()).await?).await {
+                Err(e) if db::is_unique_violation(&e) => return ctx.render(FORM, context!{ name, error => "Ce nom est déjà pris" }),
+                id => id?
+            };
+            println!("Created user: {}", name);
+            let cookie = sessions::start(&ctx.db, id, ctx.secure_cookies()).await?;
+            logged_in(ctx.render(FORM, context!{ message => format!("Compte {} créé", name) })?, cookie)
+        }
+    }
+}
+
+/// Set the session cookie and ask htmx to refresh the page so it shows the user
+fn logged_in(res: Option<http::Response<Vec<u8>>>, cookie: String) -> HandlerResult {
+    let mut res = res.unwrap();
+    let headers = res.headers_mut();
+    headers.insert(http::header::SET_COOKIE, http::HeaderValue::from_str(&cookie).unwrap());
+    headers.insert("hx-refresh", http::HeaderValue::from_static("true"));
+    Ok(Some(res))
+}
+
+async fn logout(ctx: RequestContext) -> HandlerResult {
+    let cookie = sessions::end(&ctx.db, &ctx.req, ctx.secure_cookies()).await?;
+    let res = http::Response::builder()
+        .status(http::StatusCode::NO_CONTENT)
+        .header(http::header::SET_COOKIE, cookie)
+        .header("hx-refresh", "true")
+        .body(Vec::new())
+        .unwrap();
+    Ok(Some(res))
+}
+
+/// Home page, with the timetable of the user
+async fn index(ctx: RequestContext) -> HandlerResult {
+    let timetable = timetable_context(&ctx).await?;
+    ctx.render_page("/index.html", context!{ timetable })
+}
+
+/// Timetable of a week, `week` is any day of the week (the current one by default).
+///
+/// The lessons can be filtered with the `class`, `type`, `teacher`, `room` and `homework` arguments.
+async fn timetable(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ViewClasses)?;
+    let filters = timetable::Filters::from_args(&ctx.args.0).ok_or(HandleError::BadRequest)?;
+    let timetable = timetable_context(&ctx).await?;
+    let mut res = ctx.render("/components/timetable.html", context!{ timetable })?.unwrap();
+    // Show the week and the filters in the url of the page, so it can be bookmarked
+    let week = ctx.args.0.get("week")
+        .and_then(|w| dates::parse_day(w))
+        .map(timetable::week_start);
+    let url = match filters.query(week).as_str() {
+        "" => "/".to_string(),
+        query => format!("/?{}", query)
+    };
+    res.headers_mut().insert("hx-push-url", http::HeaderValue::from_str(&url).unwrap());
+    Ok(Some(res))
+}
+
+/// Context of the timetable template, `None` if nobody is logged in
+async fn timetable_context(ctx: &RequestContext) -> Result<Option<minijinja::Value>, HandleError> {
+    if !ctx.permissions.can(Permission::ViewClasses) {
+        return Ok(None);
+    }
+    let day = match ctx.args.0.get("week") {
+        None => Local::now().date_naive(),
+        Some(week) => dates::parse_day(week).ok_or(HandleError::BadRequest)?
+    };
+    let filters = timetable::Filters::from_args(&ctx.args.0).ok_or(HandleError::BadRequest)?;
+    let class_ids = if ctx.permissions.is_admin() {
+        None
+    } else {
+        Some(ctx.permissions.classes())
+    };
+    let week = timetable::week(&ctx.db, timetable::week_start(day), class_ids, &filters).await?;
+    // The occurrences can be changed by the users managing lessons, the server checks each one
+    let manage_lessons = ctx.permissions.can_any(Permission::ManageLessons);
+    Ok(Some(context!{ manage_lessons, ..week }))
+}
+
+const HOMEWORK_FORM: &str = "/components/top_bar/adding_popup/homework_form.html";
+const HOMEWORK_LIST: &str = "/components/homeworks.html";
+
+/// Homeworks still to do in the classes of the user, grouped by due date then by class
+async fn list_homeworks(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ViewClasses)?;
+    render_homeworks(&ctx).await
+}
+
+async fn render_homeworks(ctx: &RequestContext) -> HandlerResult {
+    let mut filter = HomeworkFilter {
+        due_from: Some(Local::now().date_naive()),
+        ..Default::default()
+    };
+    if !ctx.permissions.is_admin() {
+        filter.class_ids = Some(ctx.permissions.classes());
+    }
+    if let Some(class) = ctx.args.0.get("class") {
+        filter.class_id = Some(class.parse().map_err(|_| HandleError::BadRequest)?);
+    }
+    let homeworks = ctx.db.list_homeworks(filter).await?;
+    let class_names = ctx.db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect::<HashMap<_, _>>();
+
+    // The homeworks are sorted by due date then by class
+    let mut days = Vec::new();
+    let mut homeworks = homeworks.into_iter().peekable();
+    while let Some(first) = homeworks.peek() {
+        let due_date = first.due_date;
+        let mut classes = Vec::new();
+        while let Some(first) = homeworks.peek().filter(|h| h.due_date == due_date) {
+            let class_id = first.class_id;
+            let mut class_homeworks = Vec::new();
+            while let Some(homework) = homeworks.next_if(|h| h.due_date == due_date && h.class_id == class_id) {
+                class_homeworks.push(minijinja::Value::from_struct_object(homework));
+            }
+            classes.push(context!{
+                name => class_names.get(&class_id).cloned().unwrap_or_default(),
+                manage => ctx.permissions.can(Permission::ManageHomeworks(class_id)),
+                homeworks => class_homeworks
+            });
+        }
+        days.push(context!{ date => dates::format_day(due_date), classes });
+    }
+    ctx.render(HOMEWORK_LIST, context!{ days })
+}
+
+async fn homework_form(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check_any(Permission::ManageHomeworks)?;
+    render_homework_form(&ctx, context!{}).await
+}
+
+/// Render the homework form with the classes and lessons the user can choose
+async fn render_homework_form(ctx: &RequestContext, extra: minijinja::Value) -> HandlerResult {
+    let classes = ctx.db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .filter(|c| ctx.permissions.can(Permission::ManageHomeworks(c.id)))
+        .collect::<Vec<_>>();
+
+    // Lessons of the next weeks in these classes
+    let now = Local::now().naive_local();
+    let lesson_types = ctx.db.list_lesson_types(false).await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect::<HashMap<_, _>>();
+    let mut lessons = Vec::new();
+    for class in classes.iter() {
+        lessons.extend(ctx.db.list_occurrences(LessonFilter {
+            class_id: Some(class.id),
+            from: Some(now),
+            to: Some(now + Duration::weeks(4)),
+            ..Default::default()
+        }).await?.into_iter().filter(|o| !o.cancelled));
+    }
+    // Recurring lessons are shown with their next occurrence
+    lessons.sort_by_key(|o| (o.lesson.id, o.start));
+    lessons.dedup_by_key(|o| o.lesson.id);
+    lessons.sort_by_key(|o| (o.start, o.lesson.id));
+
+    let classes = classes.into_iter()
+        .map(|c| context!{ id => c.id, name => c.name })
+        .collect::<Vec<_>>();
+    let lessons = lessons.into_iter()
+        .map(|o| context!{
+            id => o.lesson.id,
+            label => format!("{} - {}", lesson_types.get(&o.lesson.type_id).cloned().unwrap_or_default(), dates::format_datetime(o.start))
+        })
+        .collect::<Vec<_>>();
+    ctx.render(HOMEWORK_FORM, context!{ classes, lessons, ..extra })
+}
+
+async fn create_homework(ctx: RequestContext) -> HandlerResult {
+    let user = match &ctx.user {
+        None => return Err(HandleError::Unauthorized),
+        Some(u) => u.clone()
+    };
+    let args = ctx.form();
+    let values = minijinja::Value::from_struct_object(args.clone());
+    let error = |error: &str| context!{ values => values.clone(), error };
+
+    let name = args.0.get("name").cloned().unwrap_or_default();
+    if name.trim().is_empty() {
+        return render_homework_form(&ctx, error("Veuillez entrer un titre")).await;
+    }
+    let class_id = match args.0.get("class").and_then(|c| c.parse().ok()) {
+        None => return render_homework_form(&ctx, error("Veuillez choisir une classe")).await,
+        Some(c) => c
+    };
+    ctx.permissions.check(Permission::ManageHomeworks(class_id))?;
+    if ctx.db.get_class(class_id).await?.is_none() {
+        return render_homework_form(&ctx, error("Cette classe n'existe pas")).await;
+    }
+    let due_date = match args.0.get("due_date").and_then(|d| dates::parse_day(d)) {
+        None => return render_homework_form(&ctx, error("Veuillez choisir une date")).await,
+        Some(d) => d
+    };
+    let lesson_id = match args.0.get("lesson").filter(|l| !l.is_empty()) {
+        None => None,
+        Some(lesson) => {
+            let lesson_id = lesson.parse().map_err(|_| HandleError::BadRequest)?;
+            let in_class = ctx.db.list_lesson_classes(lesson_id, false).await?
+                .iter()
+                .any(|c| c.class_id == class_id);
+            if !in_class {
+                return render_homework_form(&ctx, error("Ce cours n'est pas dans cette classe")).await;
+            }
+            Some(lesson_id)
+        }
+    };
+
+    ctx.db.insert_homework(HomeworkData {
+        name: name.trim().to_string(),
+        description: args.0.get("description").cloned().unwrap_or_default(),
+        class_id,
+        lesson_id,
+        due_date
+    }, user.id).await?;
+    println!("Homework created by {}: {}", user.name, name);
+
+    let mut res = render_homework_form(&ctx, context!{ message => "Devoir ajouté" }).await?.unwrap();
+    // Refresh the list of homeworks
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("homeworks-changed"));
+    Ok(Some(res))
+}
+
+async fn delete_homework(ctx: RequestContext) -> HandlerResult {
+    // Checked before looking for the homework, so that the ids can't be probed
+    ctx.permissions.check_any(Permission::ManageHomeworks)?;
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    let homework = match ctx.db.get_homework(id).await? {
+        None => return Err(HandleError::NotFound),
+        Some(h) => h
+    };
+    ctx.permissions.check(Permission::ManageHomeworks(homework.class_id))?;
+    ctx.db.delete_homework(id).await?;
+    render_homeworks(&ctx).await
+}
+
+const LESSON_FORM: &str = "/components/top_bar/adding_popup/lesson_form.html";
+
+async fn lesson_form(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check_any(Permission::ManageLessons)?;
+    render_lesson_form(&ctx, context!{}).await
+}
+
+/// Form filled with the current values of the lesson
+async fn edit_lesson_form(ctx: RequestContext) -> HandlerResult {
+    let (lesson, class_ids) = editable_lesson(&ctx).await?;
+    let mut values = HttpArgs::new();
+    values.insert("id".to_string(), lesson.id.to_string());
+    values.insert("type".to_string(), lesson.type_id.to_string());
+    for class_id in class_ids {
+        values.insert(format!("class_{}", class_id), "1".to_string());
+    }
+    values.insert("date".to_string(), lesson.start.date().to_string());
+    values.insert("start".to_string(), lesson.start.format("%H:%M").to_string());
+    values.insert("end".to_string(), lesson.end.format("%H:%M").to_string());
+    if let Some(repeat) = lesson.repeat {
+        values.insert("repeat".to_string(), repeat.weeks.to_string());
+        values.insert("until".to_string(), repeat.until.to_string());
+    }
+    for room in ctx.db.list_rooms_of_lessons(vec![lesson.id]).await? {
+        values.insert(format!("room_{}", room.room_id), "1".to_string());
+    }
+    for teacher in ctx.db.list_teachers_of_lessons(vec![lesson.id]).await? {
+        values.insert(format!("teacher_{}", teacher.teacher_id), "1".to_string());
+    }
+    render_lesson_form(&ctx, context!{ values => minijinja::Value::from_struct_object(values) }).await
+}
+
+/// Render the lesson form with the lesson types, rooms, teachers and the classes the user can choose
+async fn render_lesson_form(ctx: &RequestContext, extra: minijinja::Value) -> HandlerResult {
+    let types = ctx.db.list_lesson_types(false).await?
+        .into_iter()
+        .map(|t| context!{ id => t.id, name => t.name })
+        .collect::<Vec<_>>();
+    let classes = ctx.db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .filter(|c| ctx.permissions.can(Permission::ManageLessons(c.id)))
+        .map(|c| context!{ id => c.id, name => c.name })
+        .collect::<Vec<_>>();
+    let rooms = ctx.db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| context!{ id => r.id, name => r.name })
+        .collect::<Vec<_>>();
+    let teachers = ctx.db.list_teachers(false).await?
+        .into_iter()
+        .map(|t| context!{ id => t.id, name => t.name })
+        .collect::<Vec<_>>();
+    // Only admins can save a lesson despite its conflicts
+    ctx.render(LESSON_FORM, context!{ types, classes, rooms, teachers, is_admin => ctx.permissions.is_admin(), ..extra })
+}
+
+/// The lesson of the `id` parameter and its classes, if the user can manage the lessons of all these classes
+async fn editable_lesson(ctx: &RequestContext) -> Result<(Lesson, Vec<usize>), HandleError> {
+    // Checked before looking for the lesson, so that the ids can't be probed
+    ctx.permissions.check_any(Permission::ManageLessons)?;
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    let lesson = match ctx.db.get_lesson(id).await? {
+        None => return Err(HandleError::NotFound),
+        Some(l) => l
+    };
+    let class_ids = ctx.db.list_lesson_classes(id, false).await?
+        .into_iter()
+        .map(|c| c.class_id)
+        .collect::<Vec<_>>();
+    // Only admins can edit the lessons without classes
+    if (class_ids.is_empty() && !ctx.permissions.is_admin()) || !class_ids.iter().all(|c| ctx.permissions.can(Permission::ManageLessons(*c))) {
+        return Err(HandleError::Forbidden);
+    }
+    Ok((lesson, class_ids))
+}
+
+async fn create_lesson(ctx: RequestContext) -> HandlerResult {
+    save_lesson(ctx, None).await
+}
+
+async fn update_lesson(ctx: RequestContext) -> HandlerResult {
+    let (lesson, _) = editable_lesson(&ctx).await?;
+    save_lesson(ctx, Some(lesson.id)).await
+}
+
+/// Ids of the checked checkboxes named `<prefix>_<id>`
+fn checked_ids(args: &HttpArgs, prefix: &str) -> Vec<usize> {
+    let mut ids = args.0.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .filter_map(|(name, _)| name.strip_prefix(prefix)?.strip_prefix('_')?.parse().ok())
+        .collect::<Vec<usize>>();
+    ids.sort();
+    ids
+}
+
+/// Create or update a lesson from the form.
+///
+/// If it conflicts with other lessons, the form is shown again with the conflicts, unless an admin gave a reason to save it anyway
+async fn save_lesson(ctx: RequestContext, lesson_id: Option<usize>) -> HandlerResult {
+    let user = match &ctx.user {
+        None => return Err(HandleError::Unauthorized),
+        Some(u) => u.clone()
+    };
+    let args = ctx.form();
+    let mut values = args.clone();
+    if let Some(lesson_id) = lesson_id {
+        values.insert("id".to_string(), lesson_id.to_string());
+    }
+    let values = minijinja::Value::from_struct_object(values);
+    let error = |error: &str| context!{ values => values.clone(), error };
+
+    let type_id = match args.0.get("type").and_then(|t| t.parse().ok()) {
+        None => return render_lesson_form(&ctx, error("Veuillez choisir une matière")).await,
+        Some(t) => t
+    };
+    if ctx.db.get_lesson_type(type_id).await?.is_none() {
+        return render_lesson_form(&ctx, error("Cette matière n'existe pas")).await;
+    }
+    // One checkbox per class, room and teacher
+    let class_ids = checked_ids(&args, "class");
+    if class_ids.is_empty() {
+        return render_lesson_form(&ctx, error("Veuillez choisir au moins une classe")).await;
+    }
+    for class_id in class_ids.iter() {
+        ctx.permissions.check(Permission::ManageLessons(*class_id))?;
+        if ctx.db.get_class(*class_id).await?.is_none() {
+            return render_lesson_form(&ctx, error("Cette classe n'existe pas")).await;
+        }
+    }
+    let room_ids = checked_ids(&args, "room");
+    for room_id in room_ids.iter() {
+        if ctx.db.get_room(*room_id).await?.is_none() {
+            return render_lesson_form(&ctx, error("Cette salle n'existe pas")).await;
+        }
+    }
+    let teacher_ids = checked_ids(&args, "teacher");
+    for teacher_id in teacher_ids.iter() {
+        if ctx.db.get_teacher(*teacher_id).await?.is_none() {
+            return render_lesson_form(&ctx, error("Ce professeur n'existe pas")).await;
+        }
+    }
+    let date = match args.0.get("date").and_then(|d| dates::parse_day(d)) {
+        None => return render_lesson_form(&ctx, error("Veuillez choisir une date")).await,
+        Some(d) => d
+    };
+    let (start, end) = match (args.0.get("start").and_then(|t| dates::parse_time(t)), args.0.get("end").and_then(|t| dates::parse_time(t))) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return render_lesson_form(&ctx, error("Veuillez entrer une heure de début et une heure de fin plus tard")).await
+    };
+    let repeat = match args.0.get("repeat").map(|r| r.as_str()) {
+        None | Some("") => None,
+        Some(weeks) => {
+            let weeks = weeks.parse().ok().filter(|w| (1..=2).contains(w)).ok_or(HandleError::BadRequest)?;
+            match args.0.get("until").and_then(|d| dates::parse_day(d)).filter(|until| *until >= date) {
+                None => return render_lesson_form(&ctx, error("Veuillez choisir la date du dernier cours, après le premier")).await,
+                Some(until) => Some(Repeat { weeks, until })
+            }
+        }
+    };
+    let data = LessonData {
+        type_id,
+        start: date.and_time(start),
+        end: date.and_time(end),
+        repeat
+    };
+    let resources = Resources { class_ids, room_ids, teacher_ids };
+
+    let conflicts = conflicts::find(&ctx.db, lesson_id, &data, &resources).await?;
+    let reason = args.0.get("reason").cloned().unwrap_or_default();
+    if !conflicts.is_empty() && (reason.is_empty() || !ctx.permissions.is_admin()) {
+        let conflicts = conflicts.into_iter()
+            .map(minijinja::Value::from_struct_object)
+            .collect::<Vec<_>>();
+        return render_lesson_form(&ctx, context!{ conflicts, ..error("Ce cours est en conflit avec d'autres cours") }).await;
+    }
+
+    // Keep the values when editing, to change them again
+    let (lesson_id, extra) = match lesson_id {
+        None => (ctx.db.insert_lesson(data).await?, context!{ message => "Cours ajouté" }),
+        Some(lesson_id) => {
+            ctx.db.update_lesson(lesson_id, data).await?;
+            (lesson_id, context!{ values, message => "Cours modifié" })
+        }
+    };
+    ctx.db.set_lesson_classes(lesson_id, resources.class_ids).await?;
+    ctx.db.set_lesson_rooms(lesson_id, resources.room_ids).await?;
+    ctx.db.set_lesson_teachers(lesson_id, resources.teacher_ids).await?;
+    if !conflicts.is_empty() {
+        let description = conflicts.iter().map(|c| c.describe()).collect::<Vec<_>>().join("\n");
+        ctx.db.insert_conflict_override(lesson_id, user.id, reason.clone(), description).await?;
+        println!("Lesson {} saved by {} despite {} conflicts: {}", lesson_id, user.name, conflicts.len(), reason);
+    }
+
+    let mut res = render_lesson_form(&ctx, extra).await?.unwrap();
+    // Refresh the timetable
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+const OCCURRENCE_FORM: &str = "/components/occurrence_form.html";
+
+/// The day of the `date` parameter, if the lesson has an occurrence planned on it
+fn occurrence_date(ctx: &RequestContext, lesson: &Lesson) -> Result<NaiveDate, HandleError> {
+    let date = ctx.param("date").and_then(dates::parse_day).ok_or(HandleError::BadRequest)?;
+    if !lesson.takes_place_on(date) {
+        return Err(HandleError::NotFound);
+    }
+    Ok(date)
+}
+
+/// Values of the occurrence form for the current exception of the occurrence, empty if it takes place as planned
+async fn occurrence_values(ctx: &RequestContext, lesson_id: usize, date: NaiveDate) -> anyhow::Result<minijinja::Value> {
+    let mut values = HttpArgs::new();
+    let exception = ctx.db.list_lesson_exceptions(vec![lesson_id]).await?
+        .into_iter()
+        .find(|e| e.date == date);
+    if let Some(exception) = exception {
+        if exception.cancelled {
+            values.insert("status".to_string(), "cancelled".to_string());
+        }
+        if let Some((start, end)) = exception.moved_to {
+            values.insert("status".to_string(), "moved".to_string());
+            values.insert("day".to_string(), start.date().to_string());
+            values.insert("start".to_string(), start.format("%H:%M").to_string());
+            values.insert("end".to_string(), end.format("%H:%M").to_string());
+        }
+        if let Some(room_id) = exception.room_id {
+            values.insert("room".to_string(), room_id.to_string());
+        }
+    }
+    Ok(minijinja::Value::from_struct_object(values))
+}
+
+/// Form to cancel one occurrence of a lesson, move it or change its room
+async fn occurrence_form(ctx: RequestContext) -> HandlerResult {
+    let (lesson, _) = editable_lesson(&ctx).await?;
+    let date = occurrence_date(&ctx, &lesson)?;
+    let values = occurrence_values(&ctx, lesson.id, date).await?;
+    render_occurrence_form(&ctx, &lesson, date, context!{ values }).await
+}
+
+/// Render the occurrence form with the rooms the occurrence can be moved to
+async fn render_occurrence_form(ctx: &RequestContext, lesson: &Lesson, date: NaiveDate, extra: minijinja::Value) -> HandlerResult {
+    let name = ctx.db.get_lesson_type(lesson.type_id).await?
+        .map(|t| t.name)
+        .unwrap_or_default();
+    let rooms = ctx.db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| context!{ id => r.id, name => r.name })
+        .collect::<Vec<_>>();
+    let has_exception = ctx.db.list_lesson_exceptions(vec![lesson.id]).await?
+        .iter()
+        .any(|e| e.date == date);
+    ctx.render(OCCURRENCE_FORM, context!{
+        lesson_id => lesson.id,
+        date => date.to_string(),
+        label => format!("{} du {}", name, dates::format_day(date)),
+        rooms,
+        has_exception,
+        is_admin => ctx.permissions.is_admin(),
+        ..extra
+    })
+}
+
+/// Cancel an occurrence, move it or change its room. Without any change, it takes place as planned again.
+///
+/// Like the lessons, a moved occurrence or its new room is checked for conflicts, which only an admin can ignore
+async fn save_occurrence(ctx: RequestContext) -> HandlerResult {
+    let (lesson, class_ids) = editable_lesson(&ctx).await?;
+    let date = occurrence_date(&ctx, &lesson)?;
+    let user = ctx.user.clone().ok_or(HandleError::Unauthorized)?;
+    let args = ctx.form();
+    let values = minijinja::Value::from_struct_object(args.clone());
+    let error = |error: &str| context!{ values => values.clone(), error };
+
+    let room_id = match args.0.get("room").filter(|r| !r.is_empty()) {
+        None => None,
+        Some(room) => {
+            let room_id = room.parse().map_err(|_| HandleError::BadRequest)?;
+            if ctx.db.get_room(room_id).await?.is_none() {
+                return render_occurrence_form(&ctx, &lesson, date, error("Cette salle n'existe pas")).await;
+            }
+            Some(room_id)
+        }
+    };
+    let (cancelled, moved_to) = match args.0.get("status").map(|s| s.as_str()) {
+        None | Some("") => (false, None),
+        Some("cancelled") => (true, None),
+        Some("moved") => {
+            let day = match args.0.get("day").and_then(|d| dates::parse_day(d)) {
+                None => return render_occurrence_form(&ctx, &lesson, date, error("Veuillez choisir une date")).await,
+                Some(d) => d
+            };
+            match (args.0.get("start").and_then(|t| dates::parse_time(t)), args.0.get("end").and_then(|t| dates::parse_time(t))) {
+                (Some(start), Some(end)) if start < end => (false, Some((day.and_time(start), day.and_time(end)))),
+                _ => return render_occurrence_form(&ctx, &lesson, date, error("Veuillez entrer une heure de début et une heure de fin plus tard")).await
+            }
+        }
+        Some(_) => return Err(HandleError::BadRequest)
+    };
+
+    if !cancelled && moved_to.is_none() && room_id.is_none() {
+        ctx.db.delete_lesson_exception(lesson.id, date).await?;
+        return occurrence_changed(&ctx, &lesson, date, "Séance rétablie").await;
+    }
+
+    let mut conflicts = Vec::new();
+    if !cancelled {
+        let (start, end) = moved_to.unwrap_or_else(|| {
+            let start = date.and_time(lesson.start.time());
+            (start, start + (lesson.end - lesson.start))
+        });
+        let data = LessonData { type_id: lesson.type_id, start, end, repeat: None };
+        let room_ids = match room_id {
+            None => ctx.db.list_rooms_of_lessons(vec![lesson.id]).await?.into_iter().map(|r| r.room_id).collect(),
+            Some(room_id) => vec![room_id]
+        };
+        let teacher_ids = ctx.db.list_teachers_of_lessons(vec![lesson.id]).await?
+            .into_iter()
+            .map(|t| t.teacher_id)
+            .collect();
+        conflicts = conflicts::find(&ctx.db, Some(lesson.id), &data, &Resources { class_ids, room_ids, teacher_ids }).await?;
+    }
+    let reason = args.0.get("reason").cloned().unwrap_or_default();
+    if !conflicts.is_empty() && (reason.is_empty() || !ctx.permissions.is_admin()) {
+        let conflicts = conflicts.into_iter()
+            .map(minijinja::Value::from_struct_object)
+            .collect::<Vec<_>>();
+        return render_occurrence_form(&ctx, &lesson, date, context!{ conflicts, ..error("Cette séance est en conflit avec d'autres cours") }).await;
+    }
+
+    ctx.db.set_lesson_exception(LessonException { lesson_id: lesson.id, date, cancelled, moved_to, room_id }).await?;
+    if !conflicts.is_empty() {
+        let description = conflicts.iter().map(|c| c.describe()).collect::<Vec<_>>().join("\n");
+        ctx.db.insert_conflict_override(lesson.id, user.id, reason.clone(), description).await?;
+        println!("Occurrence {} of the lesson {} saved by {} despite {} conflicts: {}", date, lesson.id, user.name, conflicts.len(), reason);
+    }
+    occurrence_changed(&ctx, &lesson, date, "Séance modifiée").await
+}
+
+/// Remove the exception of an occurrence, so it takes place as planned
+async fn restore_occurrence(ctx: RequestContext) -> HandlerResult {
+    let (lesson, _) = editable_lesson(&ctx).await?;
+    let date = occurrence_date(&ctx, &lesson)?;
+    if !ctx.db.delete_lesson_exception(lesson.id, date).await? {
+        return Err(HandleError::NotFound);
+    }
+    occurrence_changed(&ctx, &lesson, date, "Séance rétablie").await
+}
+
+/// The occurrence form with its new values, refreshing the timetable
+async fn occurrence_changed(ctx: &RequestContext, lesson: &Lesson, date: NaiveDate, message: &str) -> HandlerResult {
+    let values = occurrence_values(ctx, lesson.id, date).await?;
+    let mut res = render_occurrence_form(ctx, lesson, date, context!{ values, message }).await?.unwrap();
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+/// Join a class as a student with its join code
+async fn join_class(ctx: RequestContext) -> HandlerResult {
+    const FORM: &str = "/components/top_bar/adding_popup/join_form.html";
+    ctx.permissions.check(Permission::JoinClass)?;
+    let user = ctx.user.clone().ok_or(HandleError::Unauthorized)?;
+    let args = ctx.form();
+    let code = args.0.get("code").cloned().unwrap_or_default();
+    let class = match ctx.db.get_class_by_join_code(&code).await? {
+        None => return ctx.render(FORM, context!{ code, error => "Ce code ne correspond à aucune classe" }),
+        Some(c) => c
+    };
+    if ctx.db.get_membership(user.id, class.id).await?.is_some() {
+        return ctx.render(FORM, context!{ error => format!("Vous êtes déjà dans la classe {}", class.name) });
+    }
+
+    ctx.db.insert_membership(user.id, class.id, Role::Student).await?;
+    ctx.db.insert_membership_change(class.id, user.id, Some(user.id), MembershipAction::Join, Some(Role::Student)).await?;
+    println!("{} joined the class {}", user.name, class.name);
+    let mut res = ctx.render(FORM, context!{ message => format!("Vous avez rejoint la classe {}", class.name) })?.unwrap();
+    // Show the lessons and homeworks of the class
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed, homeworks-changed"));
+    Ok(Some(res))
+}
+
+const ROSTER: &str = "/components/roster.html";
+
+/// Members of the classes the user can manage
+async fn rosters(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check_any(Permission::ManageMembers)?;
+    let mut rosters = Vec::new();
+    for class in ctx.db.list_classes(ClassFilter::default()).await? {
+        if ctx.permissions.can(Permission::ManageMembers(class.id)) {
+            rosters.push(roster_context(&ctx, class).await?);
+        }
+    }
+    ctx.render_page("/classes.html", context!{ rosters })
+}
+
+async fn render_roster(ctx: &RequestContext, class: Class, extra: minijinja::Value) -> HandlerResult {
+    let roster = roster_context(ctx, class).await?;
+    ctx.render(ROSTER, context!{ roster, ..extra })
+}
+
+/// Members of the class, the roles the user can give them and the last changes
+async fn roster_context(ctx: &RequestContext, class: Class) -> anyhow::Result<minijinja::Value> {
+    // The changes can be about users deleted since
+    let user_names = ctx.db.list_users(UserFilter { include_deleted: true, ..Default::default() }).await?
+        .into_iter()
+        .map(|u| (u.id, u.name))
+        .collect::<HashMap<_, _>>();
+    let name = |user_id: Option<usize>| user_id.and_then(|id| user_names.get(&id).cloned()).unwrap_or("?".to_string());
+
+    let mut members = ctx.db.list_memberships(MembershipFilter { class_id: Some(class.id), ..Default::default() }).await?;
+    members.sort_by_key(|m| name(Some(m.user_id)).to_lowercase());
+    let members = members.into_iter()
+        .map(|m| context!{
+            id => m.user_id,
+            name => name(Some(m.user_id)),
+            role => m.role.as_str(),
+            role_label => m.role.label(),
+            // Users can't change their own role
+            editable => ctx.user.as_ref().is_some_and(|u| u.id != m.user_id) && ctx.permissions.can_manage_member(class.id, m.role)
+        })
+        .collect::<Vec<_>>();
+    let roles = [Role::Student, Role::Delegate, Role::Teacher].into_iter()
+        .filter(|r| ctx.permissions.can_manage_member(class.id, *r))
+        .map(|r| context!{ value => r.as_str(), label => r.label() })
+        .collect::<Vec<_>>();
+    let changes = ctx.db.list_membership_changes(class.id, 20).await?
+        .into_iter()
+        .map(|c| context!{
+            date => dates::format_datetime(c.created_at),
+            description => describe_change(&c, name(c.actor_id), name(c.user_id))
+        })
+        .collect::<Vec<_>>();
+    Ok(context!{ id => class.id, name => class.name, join_code => class.join_code, members, roles, changes })
+}
+
+fn describe_change(change: &MembershipChange, actor: String, user: String) -> String {
+    match change.action {
+        MembershipAction::Join => format!("{} a rejoint la classe", user),
+        MembershipAction::Remove => format!("{} a retiré {} de la classe", actor, user),
+        MembershipAction::ChangeRole => format!("{} a donné le rôle {} à {}", actor, change.role.map(|r| r.label()).unwrap_or_default(), user),
+        MembershipAction::RotateCode => format!("{} a changé le code pour rejoindre la classe", actor)
+    }
+}
+
+/// The class of the `id` parameter, if the user can manage its members
+async fn managed_class(ctx: &RequestContext) -> Result<Class, HandleError> {
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    ctx.permissions.check(Permission::ManageMembers(id))?;
+    match ctx.db.get_class(id).await? {
+        None => Err(HandleError::NotFound),
+        Some(c) => Ok(c)
+    }
+}
+
+/// Membership of the `user` parameter in the class, if the user can manage it
+async fn managed_member(ctx: &RequestContext, class: &Class) -> Result<Membership, HandleError> {
+    let user_id = ctx.param("user").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    let membership = match ctx.db.get_membership(user_id, class.id).await? {
+        None => return Err(HandleError::NotFound),
+        Some(m) => m
+    };
+    if !ctx.permissions.can_manage_member(class.id, membership.role) {
+        return Err(HandleError::Forbidden);
+    }
+    Ok(membership)
+}
+
+/// Replace the join code of the class, when the old one was given to people who shouldn't join it
+async fn rotate_join_code(ctx: RequestContext) -> HandlerResult {
+    let user = match &ctx.user {
+        None => return Err(HandleError::Unauthorized),
+        Some(u) => u.clone()
+    };
+    let mut class = managed_class(&ctx).await?;
+    class.join_code = match ctx.db.rotate_join_code(class.id).await? {
+        None => return Err(HandleError::NotFound),
+        Some(c) => c
+    };
+    ctx.db.insert_membership_change(class.id, user.id, None, MembershipAction::RotateCode, None).await?;
+    println!("{} changed the join code of {}", user.name, class.name);
+    render_roster(&ctx, class, context!{ message => "Le code a été changé, l'ancien ne fonctionne plus" }).await
+}
+
+async fn change_member_role(ctx: RequestContext) -> HandlerResult {
+    let user = match &ctx.user {
+        None => return Err(HandleError::Unauthorized),
+        Some(u) => u.clone()
+    };
+    let class = managed_class(&ctx).await?;
+    let membership = managed_member(&ctx, &class).await?;
+    let args = ctx.form();
+    let role = match args.0.get("role").and_then(|r| Role::parse(r)) {
+        None | Some(Role::Admin) => return Err(HandleError::BadRequest),
+        Some(r) => r
+    };
+    if membership.user_id == user.id {
+        return render_roster(&ctx, class, context!{ error => "Vous ne pouvez pas changer votre propre rôle" }).await;
+    }
+    if !ctx.permissions.can_manage_member(class.id, role) {
+        return Err(HandleError::Forbidden);
+    }
+
+    ctx.db.set_membership_role(membership.user_id, class.id, role).await?;
+    ctx.db.insert_membership_change(class.id, user.id, Some(membership.user_id), MembershipAction::ChangeRole, Some(role)).await?;
+    println!("{} gave the role {} to user {} in {}", user.name, role.as_str(), membership.user_id, class.name);
+    render_roster(&ctx, class, context!{ message => "Rôle modifié" }).await
+}
+
+async fn remove_member(ctx: RequestContext) -> HandlerResult {
+    let user = match &ctx.user {
+        None => return Err(HandleError::Unauthorized),
+        Some(u) => u.clone()
+    };
+    let class = managed_class(&ctx).await?;
+    let membership = managed_member(&ctx, &class).await?;
+    if membership.user_id == user.id {
+        return render_roster(&ctx, class, context!{ error => "Vous ne pouvez pas vous retirer de la classe" }).await;
+    }
+
+    ctx.db.delete_membership(membership.user_id, class.id).await?;
+    ctx.db.insert_membership_change(class.id, user.id, Some(membership.user_id), MembershipAction::Remove, None).await?;
+    println!("{} removed user {} from {}", user.name, membership.user_id, class.name);
+    render_roster(&ctx, class, context!{ message => "Membre retiré de la classe" }).await
+}
+
+const RESOURCES: &str = "/components/resources.html";
+
+/// Rooms and teachers that can be added to the lessons
+async fn resources(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let resources = resources_context(&ctx.db).await?;
+    ctx.render_page("/ressources.html", resources)
+}
+
+async fn render_resources(ctx: &RequestContext, extra: minijinja::Value) -> HandlerResult {
+    let resources = resources_context(&ctx.db).await?;
+    ctx.render(RESOURCES, context!{ ..extra, ..resources })
+}
+
+/// The rooms, the teachers, the users that can be linked to a teacher and the holidays
+async fn resources_context(db: &DB) -> anyhow::Result<minijinja::Value> {
+    let rooms = db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| context!{ id => r.id, name => r.name })
+        .collect::<Vec<_>>();
+    let teachers = db.list_teachers(false).await?
+        .into_iter()
+        .map(|t| context!{ id => t.id, name => t.name, user => t.user_id })
+        .collect::<Vec<_>>();
+    let users = db.list_users(UserFilter::default()).await?
+        .into_iter()
+        .map(|u| context!{ id => u.id, name => u.name })
+        .collect::<Vec<_>>();
+    let holidays = db.list_holidays(HolidayFilter::default()).await?
+        .into_iter()
+        .map(|h| context!{ id => h.id, name => h.name, start => h.start.to_string(), end => h.end.to_string() })
+        .collect::<Vec<_>>();
+    Ok(context!{ rooms, teachers, users, holidays })
+}
+
+async fn create_room(ctx: RequestContext) -> HandlerResult {
+    save_room(ctx, None).await
+}
+
+async fn rename_room(ctx: RequestContext) -> HandlerResult {
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    save_room(ctx, Some(id)).await
+}
+
+/// Create or rename a room, its name must be unique ignoring the case
+async fn save_room(ctx: RequestContext, room_id: Option<usize>) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let args = ctx.form();
+    let name = args.0.get("name").map(|n| n.trim().to_string()).unwrap_or_default();
+    if name.is_empty() {
+        return render_resources(&ctx, context!{ error => "Veuillez entrer un nom" }).await;
+    }
+    let taken = ctx.db.list_rooms(true).await?
+        .iter()
+        .any(|r| Some(r.id) != room_id && r.name.to_lowercase() == name.to_lowercase());
+    if taken {
+        return render_resources(&ctx, context!{ error => "Une salle a déjà ce nom" }).await;
+    }
+
+    let message = match room_id {
+        None => {
+            ctx.db.insert_room(name.clone()).await?;
+            "Salle ajoutée"
+        }
+        Some(room_id) => {
+            if ctx.db.get_room(room_id).await?.is_none() || !ctx.db.rename_room(room_id, name.clone()).await? {
+                return Err(HandleError::NotFound);
+            }
+            "Salle renommée"
+        }
+    };
+    println!("Room saved: {}", name);
+    render_resources(&ctx, context!{ message }).await
+}
+
+async fn delete_room(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    if !ctx.db.delete_room(id).await? {
+        return Err(HandleError::NotFound);
+    }
+    let mut res = render_resources(&ctx, context!{ message => "Salle supprimée" }).await?.unwrap();
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+async fn create_teacher(ctx: RequestContext) -> HandlerResult {
+    save_teacher(ctx, None).await
+}
+
+async fn update_teacher(ctx: RequestContext) -> HandlerResult {
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    save_teacher(ctx, Some(id)).await
+}
+
+/// Create or update a teacher. The `user` argument is the optional account of the teacher
+async fn save_teacher(ctx: RequestContext, teacher_id: Option<usize>) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let args = ctx.form();
+    let name = args.0.get("name").map(|n| n.trim().to_string()).unwrap_or_default();
+    if name.is_empty() {
+        return render_resources(&ctx, context!{ error => "Veuillez entrer un nom" }).await;
+    }
+    let user_id = match args.0.get("user").filter(|u| !u.is_empty()) {
+        None => None,
+        Some(user) => {
+            let user_id = user.parse().map_err(|_| HandleError::BadRequest)?;
+            if ctx.db.get_user(user_id).await?.is_none() {
+                return render_resources(&ctx, context!{ error => "Cet utilisateur n'existe pas" }).await;
+            }
+            let taken = ctx.db.list_teachers(true).await?
+                .iter()
+                .any(|t| Some(t.id) != teacher_id && t.user_id == Some(user_id));
+            if taken {
+                return render_resources(&ctx, context!{ error => "Cet utilisateur est déjà un autre professeur" }).await;
+            }
+            Some(user_id)
+        }
+    };
+
+    let message = match teacher_id {
+        None => {
+            ctx.db.insert_teacher(name.clone(), user_id).await?;
+            "Professeur ajouté"
+        }
+        Some(teacher_id) => {
+            if ctx.db.get_teacher(teacher_id).await?.is_none() || !ctx.db.update_teacher(teacher_id, name.clone(), user_id).await? {
+                return Err(HandleError::NotFound);
+            }
+            "Professeur modifié"
+        }
+    };
+    println!("Teacher saved: {}", name);
+    render_resources(&ctx, context!{ message }).await
+}
+
+async fn delete_teacher(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    if !ctx.db.delete_teacher(id).await? {
+        return Err(HandleError::NotFound);
+    }
+    let mut res = render_resources(&ctx, context!{ message => "Professeur supprimé" }).await?.unwrap();
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+async fn create_holiday(ctx: RequestContext) -> HandlerResult {
+    save_holiday(ctx, None).await
+}
+
+async fn update_holiday(ctx: RequestContext) -> HandlerResult {
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    save_holiday(ctx, Some(id)).await
+}
+
+/// Create or update holidays, from their `start` to their `end` day included
+async fn save_holiday(ctx: RequestContext, holiday_id: Option<usize>) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let args = ctx.form();
+    let name = args.0.get("name").map(|n| n.trim().to_string()).unwrap_or_default();
+    if name.is_empty() {
+        return render_resources(&ctx, context!{ error => "Veuillez entrer un nom" }).await;
+    }
+    let (start, end) = match (args.0.get("start").and_then(|d| dates::parse_day(d)), args.0.get("end").and_then(|d| dates::parse_day(d))) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        _ => return render_resources(&ctx, context!{ error => "Veuillez choisir le premier et le dernier jour des vacances" }).await
+    };
+
+    let message = match holiday_id {
+        None => {
+            ctx.db.insert_holiday(name.clone(), start, end).await?;
+            "Vacances ajoutées"
+        }
+        Some(holiday_id) => {
+            if ctx.db.get_holiday(holiday_id).await?.is_none() || !ctx.db.update_holiday(holiday_id, name.clone(), start, end).await? {
+                return Err(HandleError::NotFound);
+            }
+            "Vacances modifiées"
+        }
+    };
+    println!("Holidays saved: {} ({} - {})", name, start, end);
+    let mut res = render_resources(&ctx, context!{ message }).await?.unwrap();
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+async fn delete_holiday(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageResources)?;
+    let id = ctx.param("id").and_then(|id| id.parse().ok()).ok_or(HandleError::BadRequest)?;
+    if !ctx.db.delete_holiday(id).await? {
+        return Err(HandleError::NotFound);
+    }
+    let mut res = render_resources(&ctx, context!{ message => "Vacances supprimées" }).await?.unwrap();
+    res.headers_mut().insert("hx-trigger", http::HeaderValue::from_static("lessons-changed"));
+    Ok(Some(res))
+}
+
+/// Deleted items that an admin can restore
+async fn trash(ctx: RequestContext) -> HandlerResult {
+    ctx.permissions.check(Permission::ManageTrash)?;
+    let items = trash_items(&ctx).await?;
+    ctx.render_page("/corbeille.html", context!{ items })
+}
+
+async fn restore(ctx: RequestContext) -> HandlerResult {
+    const LIST: &str = "/components/trash.html";
+    ctx.permissions.check(Permission::ManageTrash)?;
+    let args = ctx.form();
+
+    let kind = match args.0.get("kind").and_then(|k| TrashKind::parse(k)) {
+        None => return Err(HandleError::BadRequest),
+        Some(k) => k
+    };
+    let id = match args.0.get("id").and_then(|id| id.parse().ok()) {
+        None => return Err(HandleError::BadRequest),
+        Some(id) => id
+    };
+
+    let restored = ctx.db.restore_item(kind, id).await?;
+    let items = trash_items(&ctx).await?;
+    if restored {
+        println!("Restored {} {}", kind.table(), id);
+        ctx.render(LIST, context!{ items, message => format!("{} restauré(e)", kind.label()) })
+    } else {
+        ctx.render(LIST, context!{ items, error => "Cet élément n'est plus dans la corbeille" })
+    }
+}
+
+async fn trash_items(ctx: &RequestContext) -> anyhow::Result<Vec<minijinja::Value>> {
+    Ok(ctx.db.list_trash().await?
+        .into_iter()
+        .map(minijinja::Value::from_struct_object)
+        .collect())
+}
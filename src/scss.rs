@@ -0,0 +1,69 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Mutex, time::SystemTime};
+
+/// Compiled stylesheets by source path, with the stamp they were compiled at
+type Cache = HashMap<PathBuf, (SystemTime, Result<String, String>)>;
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+/// Compile the SCSS file at `path` to CSS.
+///
+/// The result is cached until `stamp` changes. It should be the last modification time of all the SCSS files that can be imported, so that editing a partial also recompiles.
+pub fn compile(path: &Path, stamp: SystemTime) -> Result<String, String> {
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some((cached_stamp, css)) = cache.get(path) {
+        if *cached_stamp == stamp {
+            return css.clone();
+        }
+    }
+
+    println!("Compiling {}", path.display());
+    let css = grass::from_path(path, &grass::Options::default())
+        .map_err(|e| e.to_string());
+    cache.insert(path.to_owned(), (stamp, css.clone()));
+    css
+}
+
+/// HTML of the overlay shown on pages when templates or stylesheets failed to compile
+pub fn error_overlay(errors: &[String]) -> String {
+    let mut html = String::from(r#"<div id="scss_error_overlay" style="position: fixed; inset: 0; z-index: 1000; overflow: auto; padding: 2rem; background-color: rgba(0, 0, 0, 0.85); color: #FF5555; font-family: monospace;" onclick="this.remove()">"#);
+    html += "<h2>Erreur de compilation</h2>";
+    for error in errors {
+        html += "<pre>";
+        html += &error.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        html += "</pre>";
+    }
+    html += "</div>";
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn compilation() {
+        let dir = std::env::temp_dir().join(format!("pronote-plus-scss-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("index.scss");
+        std::fs::write(dir.join("_colors.scss"), "$green: #19AA67;").unwrap();
+        std::fs::write(&path, "@import 'colors';\n#week { .lesson { color: $green; } }").unwrap();
+
+        let stamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let css = compile(&path, stamp).unwrap();
+        assert!(css.contains("#week .lesson") && css.contains("#19AA67"), "{}", css);
+        // Cached until the stamp changes
+        std::fs::write(&path, "#week { color: red; ").unwrap();
+        assert_eq!(compile(&path, stamp), Ok(css));
+        let error = compile(&path, stamp + Duration::from_secs(1)).unwrap_err();
+        assert!(!error.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overlay() {
+        let html = error_overlay(&["expected \"}\" in <style>".to_string()]);
+        assert!(html.contains("<pre>expected \"}\" in &lt;style&gt;</pre>"));
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use http_bytes::http;
+use anyhow::Result;
+use crate::db::{DB, User};
+
+pub const COOKIE_NAME: &str = "session";
+/// Days after which a session expires if it's not used
+pub const LIFETIME_DAYS: i64 = 30;
+/// A used session is renewed when it was last renewed more than this many hours ago
+const RENEW_AFTER_HOURS: i64 = 1;
+
+fn expires_at() -> NaiveDateTime {
+    Utc::now().naive_utc() + Duration::days(LIFETIME_DAYS)
+}
+
+/// `Set-Cookie` value for the session `id`
+pub fn cookie(id: &str, secure: bool) -> String {
+    format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax{}",
+        COOKIE_NAME, id, Duration::days(LIFETIME_DAYS).num_seconds(), if secure { "; Secure" } else { "" }
+    )
+}
+
+/// `Set-Cookie` value removing the session cookie
+pub fn clear_cookie(secure: bool) -> String {
+    format!(
+        "{}=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax{}",
+        COOKIE_NAME, if secure { "; Secure" } else { "" }
+    )
+}
+
+/// Get the session id from the `Cookie` header
+pub fn session_id<T>(req: &http::Request<T>) -> Option<String> {
+    req.headers().get_all(http::header::COOKIE).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE_NAME)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Create a session for `user_id` and return the `Set-Cookie` value
+pub async fn start(db: &DB, user_id: usize, secure: bool) -> Result<String> {
+    let id = hex::encode(rand::random::<[u8; 32]>());
+    db.insert_session(id.clone(), user_id, expires_at()).await?;
+    Ok(cookie(&id, secure))
+}
+
+/// Find the user of the session sent with `req`.
+///
+/// If the session has to be renewed, also returns the new `Set-Cookie` value.
+pub async fn current_user<T>(db: &DB, req: &http::Request<T>, secure: bool) -> Result<(Option<User>, Option<String>)> {
+    let id = match session_id(req) {
+        None => return Ok((None, None)),
+        Some(id) => id
+    };
+    let (session, user) = match db.get_session(id.clone()).await? {
+        // Unknown or expired session, remove the cookie
+        None => return Ok((None, Some(clear_cookie(secure)))),
+        Some(s) => s
+    };
+    if session.expires_at - Utc::now().naive_utc() < Duration::days(LIFETIME_DAYS) - Duration::hours(RENEW_AFTER_HOURS) {
+        db.renew_session(id.clone(), expires_at()).await?;
+        return Ok((Some(user), Some(cookie(&id, secure))));
+    }
+    Ok((Some(user), None))
+}
+
+/// Remove the session sent with `req` and return the `Set-Cookie` value removing the cookie
+pub async fn end<T>(db: &DB, req: &http::Request<T>, secure: bool) -> Result<String> {
+    if let Some(id) = session_id(req) {
+        db.delete_session(id).await?;
+    }
+    Ok(clear_cookie(secure))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(cookie: Option<&str>) -> http::Request<()> {
+        let mut req = http::Request::new(());
+        if let Some(cookie) = cookie {
+            req.headers_mut().insert(http::header::COOKIE, cookie.parse().unwrap());
+        }
+        req
+    }
+
+    #[test]
+    fn cookies() {
+        assert_eq!(cookie("abc", false), "session=abc; Max-Age=2592000; Path=/; HttpOnly; SameSite=Lax");
+        assert_eq!(clear_cookie(true), "session=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax; Secure");
+        assert_eq!(session_id(&request(Some("theme=dark; session=abc"))), Some("abc".to_string()));
+        assert_eq!(session_id(&request(Some("sessions=abc"))), None);
+        assert_eq!(session_id(&request(None)), None);
+    }
+
+    #[tokio::test]
+    async fn expiration_and_renewal() {
+        let db = DB::new(None).await.unwrap();
+        let user = db.insert_user("alice".to_string(), "hash".to_string()).await.unwrap();
+        assert!(current_user(&db, &request(None), false).await.unwrap().0.is_none());
+        let (unknown, cookie) = current_user(&db, &request(Some("session=unknown")), false).await.unwrap();
+        assert_eq!((unknown.is_none(), cookie), (true, Some(clear_cookie(false))));
+
+        let now = Utc::now().naive_utc();
+        db.insert_session("expired".to_string(), user, now - Duration::minutes(1)).await.unwrap();
+        let (expired, cookie) = current_user(&db, &request(Some("session=expired")), false).await.unwrap();
+        assert_eq!((expired.is_none(), cookie), (true, Some(clear_cookie(false))));
+
+        // A session just started is not renewed at each request
+        let fresh = start(&db, user, false).await.unwrap();
+        let fresh = fresh.split_once(';').unwrap().0;
+        let (fresh_user, cookie) = current_user(&db, &request(Some(fresh)), false).await.unwrap();
+        assert_eq!((fresh_user.map(|u| u.id), cookie), (Some(user), None));
+
+        // A session used a day ago is extended to LIFETIME_DAYS from now
+        db.insert_session("old".to_string(), user, now + Duration::days(LIFETIME_DAYS - 1)).await.unwrap();
+        let (old_user, cookie) = current_user(&db, &request(Some("session=old")), true).await.unwrap();
+        assert_eq!((old_user.map(|u| u.id), cookie), (Some(user), Some(super::cookie("old", true))));
+        let (session, _) = db.get_session("old".to_string()).await.unwrap().unwrap();
+        assert!(session.expires_at > now + Duration::days(LIFETIME_DAYS) - Duration::minutes(1));
+        let (_, cookie) = current_user(&db, &request(Some("session=old")), true).await.unwrap();
+        assert_eq!(cookie, None);
+
+        let cookie = end(&db, &request(Some("session=old")), false).await.unwrap();
+        assert_eq!(cookie, clear_cookie(false));
+        assert!(db.get_session("old".to_string()).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use minijinja::context;
+use anyhow::Result;
+use crate::{dates, db::{ClassFilter, DB, Homework, HomeworkFilter, LessonFilter, Occurrence}};
+
+/// Hours always shown, even without lessons at these times
+const DAY_START: u32 = 8;
+const DAY_END: u32 = 18;
+
+/// Filters of the timetable, given in the query string so a filtered view can be shared
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    pub class_id: Option<usize>,
+    pub type_id: Option<usize>,
+    pub teacher_id: Option<usize>,
+    pub room_id: Option<usize>,
+    /// Only the lessons with homeworks to do for them
+    pub with_homework: bool
+}
+impl Filters {
+    /// Parse the `class`, `type`, `teacher`, `room` and `homework` arguments. Returns `None` if one is invalid
+    pub fn from_args(args: &HashMap<String, String>) -> Option<Self> {
+        let id = |name: &str| match args.get(name).map(|v| v.as_str()) {
+            None | Some("") => Some(None),
+            Some(v) => v.parse().ok().map(Some)
+        };
+        Some(Self {
+            class_id: id("class")?,
+            type_id: id("type")?,
+            teacher_id: id("teacher")?,
+            room_id: id("room")?,
+            with_homework: args.get("homework").is_some_and(|v| !v.is_empty())
+        })
+    }
+    /// Query string of the filters, for the week starting on `monday` (the current one if `None`)
+    pub fn query(&self, monday: Option<NaiveDate>) -> String {
+        let mut args = Vec::new();
+        if let Some(monday) = monday {
+            args.push(format!("week={}", monday));
+        }
+        for (name, value) in [("class", self.class_id), ("type", self.type_id), ("teacher", self.teacher_id), ("room", self.room_id)] {
+            if let Some(value) = value {
+                args.push(format!("{}={}", name, value));
+            }
+        }
+        if self.with_homework {
+            args.push("homework=1".to_string());
+        }
+        args.join("&")
+    }
+}
+
+/// Monday of the week of `date`
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Minutes since the start of the day of the occurrence, an occurrence ending on another day ends at midnight
+fn minutes(occurrence: &Occurrence, time: NaiveDateTime) -> u32 {
+    if time.date() > occurrence.start.date() {
+        24 * 60
+    } else {
+        time.hour() * 60 + time.minute()
+    }
+}
+
+/// Rooms of an occurrence: the room it was moved to, or else the rooms of its lesson
+fn rooms_of(occurrence: &Occurrence, lesson_rooms: &HashMap<usize, Vec<usize>>) -> Vec<usize> {
+    match occurrence.room_id {
+        None => lesson_rooms.get(&occurrence.lesson.id).cloned().unwrap_or_default(),
+        Some(room_id) => vec![room_id]
+    }
+}
+
+/// The homeworks of a recurring lesson are shown on the occurrence they are due for
+fn is_due_for(homework: &Homework, occurrence: &Occurrence) -> bool {
+    occurrence.lesson.repeat.is_none() || homework.due_date == occurrence.date || homework.due_date == occurrence.start.date()
+}
+
+/// Context of the timetable template for the week starting on `monday`.
+///
+/// Only the lessons of `class_ids` are shown, or all the lessons if it's `None`.
+pub async fn week(db: &DB, monday: NaiveDate, class_ids: Option<Vec<usize>>, filters: &Filters) -> Result<minijinja::Value> {
+    let week_start = monday.and_hms_opt(0, 0, 0).unwrap();
+    let options = filter_options(db, class_ids.clone()).await?;
+    let occurrences = db.list_occurrences(LessonFilter {
+        class_ids,
+        class_id: filters.class_id,
+        type_id: filters.type_id,
+        teacher_id: filters.teacher_id,
+        room_id: filters.room_id,
+        with_homework: filters.with_homework,
+        from: Some(week_start),
+        to: Some(week_start + Duration::weeks(1)),
+        ..Default::default()
+    }).await?;
+    let mut lesson_ids = occurrences.iter().map(|o| o.lesson.id).collect::<Vec<_>>();
+    lesson_ids.sort();
+    lesson_ids.dedup();
+
+    let type_names = db.list_lesson_types(false).await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect::<HashMap<_, _>>();
+    let class_names = db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect::<HashMap<_, _>>();
+    let mut lesson_classes: HashMap<usize, Vec<String>> = HashMap::new();
+    for lesson_class in db.list_classes_of_lessons(lesson_ids.clone()).await? {
+        if let Some(name) = class_names.get(&lesson_class.class_id) {
+            lesson_classes.entry(lesson_class.lesson_id).or_default().push(name.clone());
+        }
+    }
+    let room_names = db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| (r.id, r.name))
+        .collect::<HashMap<_, _>>();
+    let mut lesson_rooms: HashMap<usize, Vec<usize>> = HashMap::new();
+    for lesson_room in db.list_rooms_of_lessons(lesson_ids.clone()).await? {
+        lesson_rooms.entry(lesson_room.lesson_id).or_default().push(lesson_room.room_id);
+    }
+    let teacher_names = db.list_teachers(false).await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect::<HashMap<_, _>>();
+    let mut lesson_teachers: HashMap<usize, Vec<usize>> = HashMap::new();
+    for lesson_teacher in db.list_teachers_of_lessons(lesson_ids.clone()).await? {
+        lesson_teachers.entry(lesson_teacher.lesson_id).or_default().push(lesson_teacher.teacher_id);
+    }
+    // Links to the timetable of each room and teacher, for the same week
+    let links = |ids: Vec<usize>, names: &HashMap<usize, String>, filters: fn(usize) -> Filters| ids.into_iter()
+        .filter_map(|id| Some(context!{
+            name => names.get(&id)?.clone(),
+            query => filters(id).query(Some(monday))
+        }))
+        .collect::<Vec<_>>();
+
+    let homeworks = db.list_homeworks(HomeworkFilter { lesson_ids: Some(lesson_ids), ..Default::default() }).await?;
+    let homeworks_of = |o: &Occurrence| homeworks.iter()
+        .filter(|h| h.lesson_id == Some(o.lesson.id) && is_due_for(h, o))
+        .cloned()
+        .collect::<Vec<_>>();
+    // A recurring lesson can have homeworks for only some of its occurrences, and be moved to another room
+    let occurrences = occurrences.into_iter()
+        .filter(|o| !filters.with_homework || !homeworks_of(o).is_empty())
+        .filter(|o| filters.room_id.is_none_or(|room_id| rooms_of(o, &lesson_rooms).contains(&room_id)))
+        .collect::<Vec<_>>();
+
+    // Show more hours if some lessons are outside of the usual ones
+    let first_hour = occurrences.iter()
+        .map(|o| minutes(o, o.start) / 60)
+        .fold(DAY_START, u32::min);
+    let last_hour = occurrences.iter()
+        .map(|o| minutes(o, o.end).div_ceil(60))
+        .fold(DAY_END, u32::max);
+    let day_minutes = ((last_hour - first_hour) * 60) as f64;
+    let percent = |minutes: u32| format!("{:.3}", (minutes - first_hour * 60) as f64 / day_minutes * 100.0);
+
+    let mut days = Vec::new();
+    for i in 0..7 {
+        let date = monday + Duration::days(i);
+        let day_lessons = occurrences.iter()
+            .filter(|o| o.start.date() == date)
+            .map(|o| {
+                let start = minutes(o, o.start);
+                let end = minutes(o, o.end).max(start);
+                context!{
+                    id => o.lesson.id,
+                    date => o.date.to_string(),
+                    name => type_names.get(&o.lesson.type_id).cloned().unwrap_or_default(),
+                    start => o.start.format("%H:%M").to_string(),
+                    end => o.end.format("%H:%M").to_string(),
+                    classes => lesson_classes.get(&o.lesson.id).map(|c| c.join(", ")).unwrap_or_default(),
+                    rooms => links(rooms_of(o, &lesson_rooms), &room_names, |id| Filters { room_id: Some(id), ..Default::default() }),
+                    teachers => links(lesson_teachers.get(&o.lesson.id).cloned().unwrap_or_default(), &teacher_names, |id| Filters { teacher_id: Some(id), ..Default::default() }),
+                    cancelled => o.cancelled,
+                    moved => o.moved,
+                    homeworks => homeworks_of(o).into_iter().map(minijinja::Value::from_struct_object).collect::<Vec<_>>(),
+                    top => percent(start),
+                    height => format!("{:.3}", (end - start) as f64 / day_minutes * 100.0)
+                }
+            })
+            .collect::<Vec<_>>();
+        // The week-end is only shown if there are lessons
+        if i >= 5 && day_lessons.is_empty() {
+            continue;
+        }
+        days.push(context!{
+            name => dates::weekday_name(date.weekday()),
+            date => format!("{}/{}", date.day(), date.month()),
+            lessons => day_lessons
+        });
+    }
+
+    Ok(context!{
+        label => format!("Semaine du {}", dates::format_day(monday)),
+        monday => monday.to_string(),
+        previous => filters.query(Some(monday - Duration::weeks(1))),
+        current => filters.query(None),
+        next => filters.query(Some(monday + Duration::weeks(1))),
+        filters => context!{
+            class => filters.class_id,
+            type => filters.type_id,
+            teacher => filters.teacher_id,
+            room => filters.room_id,
+            homework => filters.with_homework
+        },
+        options,
+        hours => (first_hour..last_hour).map(|h| format!("{:02}:00", h)).collect::<Vec<_>>(),
+        days
+    })
+}
+
+/// Choices of the filter panel: the classes the user can see, the lesson types, teachers and rooms
+async fn filter_options(db: &DB, class_ids: Option<Vec<usize>>) -> Result<minijinja::Value> {
+    let classes = db.list_classes(ClassFilter::default()).await?
+        .into_iter()
+        .filter(|c| class_ids.as_ref().is_none_or(|ids| ids.contains(&c.id)))
+        .map(|c| context!{ id => c.id, name => c.name })
+        .collect::<Vec<_>>();
+    let types = db.list_lesson_types(false).await?
+        .into_iter()
+        .map(|t| context!{ id => t.id, name => t.name })
+        .collect::<Vec<_>>();
+
+    let teachers = db.list_teachers(false).await?
+        .into_iter()
+        .map(|t| context!{ id => t.id, name => t.name })
+        .collect::<Vec<_>>();
+    let rooms = db.list_rooms(false).await?
+        .into_iter()
+        .map(|r| context!{ id => r.id, name => r.name })
+        .collect::<Vec<_>>();
+
+    Ok(context!{ classes, types, teachers, rooms })
+}